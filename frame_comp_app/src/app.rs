@@ -1,12 +1,13 @@
 use std::mem::size_of;
+use std::path::PathBuf;
 use std::ptr::copy_nonoverlapping as memcpy;
 use std::rc::Rc;
-use std::u64;
 
 use anyhow::{Result, anyhow};
-use cgmath::{Deg, point3, vec3};
+use cgmath::{Deg, Point3, point3, vec3};
+use log::{error, info};
 use rtcmp::RenderTargetComparator;
-use std::time::Instant;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use vk::{KhrSurfaceExtensionInstanceCommands, KhrSwapchainExtensionDeviceCommands};
 use vulkanalia::loader::{LIBRARY, LibloadingLoader};
 use vulkanalia::prelude::v1_3::*;
@@ -16,12 +17,17 @@ use vulkanalia::Version;
 use vulkanalia::window as vk_window;
 use winit::window::Window;
 
+use crate::capture::{CaptureFormat, CaptureMetadata, CaptureRequest, read_output, record_capture};
+use crate::clock::{Clock, ClockMode, DEFAULT_STEP};
 use crate::comparator::create_comparators;
+use crate::config::Config;
+use crate::metrics_log::{MetricsFormat, MetricsLog, MetricsRecord};
+use crate::recording::Recorder;
 use crate::vulkan::buffers::depth_buffer::create_depth_objects;
 use crate::vulkan::buffers::index_buffer::create_index_buffer;
 use crate::vulkan::buffers::uniform_buffer::{
-    Mat4, UniformBufferObject, create_descriptor_pool, create_descriptor_set_layout,
-    create_descriptor_sets, create_uniform_buffers,
+    CAMERA_EYE, Mat4, UniformBufferObject, create_descriptor_pool, create_descriptor_set_layout,
+    create_descriptor_sets, create_uniform_buffers, projection,
 };
 use crate::vulkan::buffers::vertex_buffer::create_vertex_buffer;
 use crate::vulkan::commands::{create_command_buffers, create_command_pool};
@@ -32,18 +38,24 @@ use crate::vulkan::image::{
 };
 use crate::vulkan::instance::create_instance;
 use crate::vulkan::model::load_model;
+use crate::vulkan::offscreen::create_offscreen_images;
 use crate::vulkan::physical_device::pick_physical_device;
 use crate::vulkan::pipeline::{create_first_pipeline, create_second_pipeline};
+use crate::vulkan::query::{create_timestamp_query_pool, get_comparator_duration_ns};
 use crate::vulkan::render_pass::create_render_pass;
 use crate::vulkan::swapchain::{create_swapchain, create_swapchain_image_views};
 use crate::vulkan::synchronization::create_sync_objects;
 use crate::vulkan::vertex::Vertex;
 
+/// The most frames in flight the app can be configured with.
 pub const MAX_FRAMES_IN_FLIGHT: usize = 3;
+/// Whether the validation layers are enabled unless configured otherwise.
 pub const VALIDATION_ENABLED: bool = true; //cfg!(debug_assertions);
 pub const VALIDATION_LAYER: vk::ExtensionName =
     vk::ExtensionName::from_bytes(b"VK_LAYER_KHRONOS_validation");
 pub const PORTABILITY_MACOS_VERSION: Version = Version::new(1, 3, 216);
+/// Measures the GPU time of the frame comparator with timestamp queries.
+pub const COMPARATOR_PROFILING_ENABLED: bool = true;
 
 /// The Vulkan App
 pub struct App {
@@ -52,26 +64,76 @@ pub struct App {
     pub data: AppData,
     pub device: Rc<Device>,
     pub frame: usize,
+    /// The number of frames rendered since the app has started.
+    pub frame_count: u64,
     pub resized: bool,
     pub start: Instant,
+    /// Receives the metrics of every rendered frame, if enabled.
+    pub metrics_log: Option<MetricsLog>,
+    /// The images to capture from the next rendered frame.
+    pub capture_request: CaptureRequest,
+    /// The file format of the captured images.
+    pub capture_format: CaptureFormat,
+    /// Saves the presented frames to an image sequence, if enabled.
+    pub recorder: Option<Recorder>,
+    /// The position of the camera, which looks at the origin.
+    pub camera_eye: Point3<f32>,
+    /// The time that animates the model.
+    pub clock: Clock,
+}
+
+/// Where the app renders to.
+#[derive(Clone, Copy, Debug)]
+pub enum RenderTarget<'a> {
+    /// The swapchain of a window.
+    Window(&'a Window),
+    /// Offscreen images of the given extent, without a window, surface or present queue,
+    /// e.g. to run comparisons on CI machines without a display.
+    Offscreen(vk::Extent2D),
 }
 
 impl App {
     /// Creates our Vulkan app.
-    pub fn create(window: &Window) -> Result<Self> {
+    pub fn create(window: &Window, config: Config) -> Result<Self> {
+        Self::create_for(RenderTarget::Window(window), config)
+    }
+
+    /// Creates our Vulkan app in headless mode, rendering into offscreen images of the size
+    /// in the config. Frames are rendered with `render_headless`.
+    pub fn create_headless(config: Config) -> Result<Self> {
+        Self::create_for(RenderTarget::Offscreen(config.extent()), config)
+    }
+
+    fn create_for(target: RenderTarget, config: Config) -> Result<Self> {
         let loader = unsafe { LibloadingLoader::new(LIBRARY) }?;
         let entry = unsafe { Entry::new(loader).map_err(|b| anyhow!("{}", b)) }?;
-        let mut data = AppData::default();
+        let mut data = AppData {
+            vbar_percentage: config.divider_position,
+            config,
+            ..Default::default()
+        };
 
-        data.vbar_percentage = 0.5;
+        let window = match target {
+            RenderTarget::Window(window) => Some(window),
+            RenderTarget::Offscreen(_) => None,
+        };
 
         let instance = create_instance(window, &entry, &mut data)?;
-        data.surface = unsafe { vk_window::create_surface(&instance, &window, &window) }?;
+        if let Some(window) = window {
+            data.surface = unsafe { vk_window::create_surface(&instance, &window, &window) }?;
+        }
         pick_physical_device(&instance, &mut data)?;
         let device = create_logical_device(&entry, &instance, &mut data)?;
         let device = Rc::new(device);
 
-        create_swapchain(window, &instance, &device, &mut data)?;
+        match target {
+            RenderTarget::Window(window) => {
+                create_swapchain(window, &instance, &device, &mut data)?
+            }
+            RenderTarget::Offscreen(extent) => {
+                create_offscreen_images(&instance, &device, &mut data, extent)?
+            }
+        }
         create_swapchain_image_views(&device, &mut data)?;
 
         let samples = data.msaa_samples;
@@ -107,6 +169,10 @@ impl App {
 
         data.frame_comparators = create_comparators(&device, &data)?;
 
+        if COMPARATOR_PROFILING_ENABLED {
+            create_timestamp_query_pool(&instance, &device, &mut data)?;
+        }
+
         create_command_buffers(&device, &mut data)?;
 
         create_sync_objects(&device, &mut data)?;
@@ -116,14 +182,30 @@ impl App {
             .extent(data.swapchain_extent)
             .build();
 
+        // Headless frames are compared across runs, so unless configured otherwise their
+        // animation mustn't depend on how long they take to render.
+        let clock_mode = data.config.clock_mode.unwrap_or(match target {
+            RenderTarget::Window(_) => ClockMode::WallClock,
+            RenderTarget::Offscreen(_) => ClockMode::FixedStep { step: DEFAULT_STEP },
+        });
+        let mut clock = Clock::new(clock_mode);
+        clock.set_time(data.config.start_time);
+
         Ok(Self {
             entry,
             instance,
             data,
             device,
             frame: 0,
+            frame_count: 0,
             resized: false,
             start: Instant::now(),
+            metrics_log: None,
+            capture_request: CaptureRequest::default(),
+            capture_format: CaptureFormat::default(),
+            recorder: None,
+            camera_eye: CAMERA_EYE.into(),
+            clock,
         })
     }
 
@@ -166,6 +248,10 @@ impl App {
 
         self.data.frame_comparators = create_comparators(&self.device, &self.data)?;
 
+        if COMPARATOR_PROFILING_ENABLED {
+            create_timestamp_query_pool(&self.instance, &self.device, &mut self.data)?;
+        }
+
         create_command_buffers(&self.device, &mut self.data)?;
 
         Ok(())
@@ -173,6 +259,10 @@ impl App {
 
     /// Destroys our Vulkan app.
     pub fn destroy(&mut self) {
+        if let Err(e) = self.stop_recording() {
+            error!("Failed to finish the recording: {:?}", e);
+        }
+
         unsafe {
             self.device.device_wait_idle().unwrap();
             self.destroy_swapchain();
@@ -209,11 +299,13 @@ impl App {
             self.device
                 .destroy_command_pool(self.data.command_pool, None);
             self.device.destroy_device(None);
-            if VALIDATION_ENABLED {
+            if self.data.config.validation {
                 self.instance
                     .destroy_debug_utils_messenger_ext(self.data.messenger, None);
             }
-            self.instance.destroy_surface_khr(self.data.surface, None);
+            if !self.data.surface.is_null() {
+                self.instance.destroy_surface_khr(self.data.surface, None);
+            }
             self.instance.destroy_instance(None);
         }
     }
@@ -223,6 +315,11 @@ impl App {
             // Destroy the comparator to free up resources.
             self.data.frame_comparators = None;
 
+            // The query pool holds one pair of timestamps per swapchain image.
+            self.device
+                .destroy_query_pool(self.data.comparator_query_pool, None);
+            self.data.comparator_query_pool = vk::QueryPool::null();
+
             // resolve image
             self.device
                 .destroy_image_view(self.data.resolve_image_view, None);
@@ -291,23 +388,25 @@ impl App {
                 .iter()
                 .for_each(|v| self.device.destroy_image_view(*v, None));
 
-            self.device.destroy_swapchain_khr(self.data.swapchain, None);
+            if self.data.swapchain.is_null() {
+                // Headless mode renders into offscreen images instead.
+                self.data
+                    .swapchain_images
+                    .iter()
+                    .for_each(|i| self.device.destroy_image(*i, None));
+                self.data
+                    .offscreen_images_memory
+                    .iter()
+                    .for_each(|m| self.device.free_memory(*m, None));
+            } else {
+                self.device.destroy_swapchain_khr(self.data.swapchain, None);
+            }
         };
     }
 
     /// Renders a frame for our Vulkan app.
     pub fn render(&mut self, window: &Window) -> Result<()> {
-        // Ensures that the GPU has finished executing the commands for the current frame
-        // (rendering & presenting) before starting a new frame. This avoids overwriting
-        // resources (like command buffers and semaphores) that are still in use.
-
-        unsafe {
-            self.device.wait_for_fences(
-                &[self.data.command_completion_fences[self.frame]],
-                true,
-                u64::MAX,
-            )
-        }?;
+        self.begin_frame()?;
 
         // This semaphore ensures synchronization between the swapchain and the rendering process.
         let this_frame_image_available_semaphore = self.data.image_available_semaphores[self.frame];
@@ -336,39 +435,174 @@ impl App {
             Err(e) => return Err(anyhow!(e)),
         };
 
+        let wait_semaphores = &[this_frame_image_available_semaphore];
+        let signal_semaphores = &[self.data.render_finished_semaphores[self.frame]];
+        self.submit_frame(image_index, wait_semaphores, signal_semaphores)?;
+
+        let swapchains = &[self.data.swapchain];
+        let image_indices = &[image_index as u32];
+        let present_info = vk::PresentInfoKHR::builder()
+            // The GPU will wait until this sepahore is signaled and it will be signaled when
+            // the command buffer above (rendering to the image) will be finished.
+            .wait_semaphores(signal_semaphores)
+            .swapchains(swapchains)
+            .image_indices(image_indices);
+
+        let result = unsafe {
+            self.device
+                .queue_present_khr(self.data.present_queue, &present_info)
+        };
+
+        let changed = result == Ok(vk::SuccessCode::SUBOPTIMAL_KHR)
+            || result == Err(vk::ErrorCode::OUT_OF_DATE_KHR);
+
+        if self.resized || changed {
+            self.resized = false;
+            self.recreate_swapchain(window)?;
+        } else if let Err(e) = result {
+            return Err(anyhow!(e));
+        }
+
+        self.end_frame()
+    }
+
+    /// Renders a frame into the offscreen images of the headless mode and returns the
+    /// index of the image that holds it. Nothing is presented, so the frame is only
+    /// visible through captures and recordings.
+    pub fn render_headless(&mut self) -> Result<usize> {
+        if !self.data.swapchain.is_null() {
+            return Err(anyhow!("The app renders to a window, not offscreen."));
+        }
+
+        self.begin_frame()?;
+
+        // Without a swapchain there is nothing to acquire, the images are used in turn.
+        let image_index = self.frame_count as usize % self.data.swapchain_images.len();
+        self.submit_frame(image_index, &[], &[])?;
+
+        self.end_frame()?;
+
+        Ok(image_index)
+    }
+
+    /// Waits until the resources of the current frame in flight can be reused.
+    fn begin_frame(&mut self) -> Result<()> {
+        // Ensures that the GPU has finished executing the commands for the current frame
+        // (rendering & presenting) before starting a new frame. This avoids overwriting
+        // resources (like command buffers and semaphores) that are still in use.
+
+        unsafe {
+            self.device.wait_for_fences(
+                &[self.data.command_completion_fences[self.frame]],
+                true,
+                u64::MAX,
+            )
+        }?;
+
+        // The frame recorded with the last use of this frame in flight has been copied.
+        if let Some(recorder) = &mut self.recorder {
+            recorder.collect(&self.device, self.data.command_pool, self.frame);
+        }
+
+        Ok(())
+    }
+
+    /// Records the captures and submits the command buffers that render into the given
+    /// image. The submission waits for `wait_semaphores` and signals `signal_semaphores`.
+    fn submit_frame(
+        &mut self,
+        image_index: usize,
+        wait_semaphores: &[vk::Semaphore],
+        signal_semaphores: &[vk::Semaphore],
+    ) -> Result<()> {
         // If a fence exists and hasn't been signaled for this image, means the GPU
         // is still processing it.
-        if !self.data.image_usage_fences[image_index as usize].is_null() {
+        if !self.data.image_usage_fences[image_index].is_null() {
             // So we need to wait for the GPU to finish its operations on this image before proceeding.
             unsafe {
                 self.device.wait_for_fences(
-                    &[self.data.image_usage_fences[image_index as usize]],
+                    &[self.data.image_usage_fences[image_index]],
                     true,
                     u64::MAX,
                 )
             }?;
+
+            // The previous submission of this image's command buffer has finished,
+            // so the timestamps written around the comparator are available.
+            if let Some(duration) =
+                get_comparator_duration_ns(&self.device, &self.data, image_index)?
+            {
+                self.data.comparator_duration_ns = Some(duration);
+            }
         }
 
         // Associates the fence for the current frame with the swapchain image
         // to track its usage.
-        self.data.image_usage_fences[image_index as usize] =
-            self.data.command_completion_fences[self.frame];
-
-        self.update_uniform_buffer(image_index)?;
+        self.data.image_usage_fences[image_index] = self.data.command_completion_fences[self.frame];
+
+        // Ticked only once an image has been acquired, so a frame that is skipped, e.g.
+        // because the swapchain is out of date, doesn't advance the fixed-step time.
+        self.clock.tick();
+        let ubo = self.update_uniform_buffer(image_index)?;
+
+        // A failed capture shouldn't stop the rendering, so the errors are only logged.
+        let request = std::mem::take(&mut self.capture_request);
+        let capture = if !request.is_empty() {
+            let metadata = CaptureMetadata {
+                frame: self.frame_count,
+                time: self.clock.time(),
+                divider_position: self.data.vbar_percentage,
+                ubo,
+            };
+            record_capture(
+                &self.instance,
+                &self.device,
+                &mut self.data,
+                image_index,
+                request,
+                self.capture_format,
+                &metadata,
+            )
+            .inspect_err(|e| error!("Failed to capture the frame: {:?}", e))
+            .ok()
+        } else {
+            None
+        };
 
-        let wait_semaphores = &[this_frame_image_available_semaphore];
+        let recording = match &mut self.recorder {
+            Some(recorder) => recorder
+                .record(
+                    &self.instance,
+                    &self.device,
+                    &mut self.data,
+                    self.frame,
+                    image_index,
+                    self.frame_count,
+                )
+                .inspect_err(|e| error!("Failed to record the frame: {:?}", e))
+                .ok()
+                .flatten(),
+            None => None,
+        };
 
         // The pipeline waits at the COLOR_ATTACHMENT_OUTPUT stage, which is where rendering
         // to the swapchain image occurs.
-        let wait_stages = &[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-        let command_buffers = &[self.data.command_buffers[image_index]];
-        let signal_semaphores = &[self.data.render_finished_semaphores[self.frame]];
+        let wait_stages =
+            vec![vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT; wait_semaphores.len()];
+        let mut command_buffers = vec![self.data.command_buffers[image_index]];
+        // The capture is executed after the frame, but before the image is presented.
+        if let Some(capture) = &capture {
+            command_buffers.push(capture.command_buffer);
+        }
+        if let Some(recording) = recording {
+            command_buffers.push(recording);
+        }
         let submit_info = vk::SubmitInfo::builder()
             // The GPU will wait with processing this command buffer until this semaphore is
             // signaled and it is signaled when the GPU is finished aquiring the image
             .wait_semaphores(wait_semaphores)
-            .wait_dst_stage_mask(wait_stages)
-            .command_buffers(command_buffers)
+            .wait_dst_stage_mask(&wait_stages)
+            .command_buffers(&command_buffers)
             // The GPU will signal this semaphore when the command buffer is done executing,
             // which means the image will be fully rendered to. We need this semaphore to be
             // signaled in order to make the GPU wait for it, before presenting this image.
@@ -394,79 +628,42 @@ impl App {
             )
         }?;
 
-        let swapchains = &[self.data.swapchain];
-        let image_indices = &[image_index as u32];
-        let present_info = vk::PresentInfoKHR::builder()
-            // The GPU will wait until this sepahore is signaled and it will be signaled when
-            // the command buffer above (rendering to the image) will be finished.
-            .wait_semaphores(signal_semaphores)
-            .swapchains(swapchains)
-            .image_indices(image_indices);
-
-        let result = unsafe {
-            self.device
-                .queue_present_khr(self.data.present_queue, &present_info)
-        };
+        // Captures are rare, so we simply wait for the frame to finish.
+        if let Some(capture) = capture {
+            unsafe {
+                self.device.wait_for_fences(
+                    &[self.data.command_completion_fences[self.frame]],
+                    true,
+                    u64::MAX,
+                )
+            }?;
+            if let Err(e) = capture.finish(&self.device, self.data.command_pool) {
+                error!("Failed to write the captured images: {:?}", e);
+            }
+        }
 
-        let changed = result == Ok(vk::SuccessCode::SUBOPTIMAL_KHR)
-            || result == Err(vk::ErrorCode::OUT_OF_DATE_KHR);
+        Ok(())
+    }
 
-        if self.resized || changed {
-            self.resized = false;
-            self.recreate_swapchain(window)?;
-        } else if let Err(e) = result {
-            return Err(anyhow!(e));
-        }
+    fn end_frame(&mut self) -> Result<()> {
+        self.log_frame_metrics()?;
 
-        self.frame = (self.frame + 1) % MAX_FRAMES_IN_FLIGHT;
+        self.frame = (self.frame + 1) % self.data.config.frames_in_flight;
+        self.frame_count += 1;
 
         Ok(())
     }
 
-    fn update_uniform_buffer(&self, image_index: usize) -> Result<()> {
-        let time = self.start.elapsed().as_secs_f32();
+    /// Writes the matrices of the current frame to the uniform buffer of the given image
+    /// and returns them.
+    fn update_uniform_buffer(&self, image_index: usize) -> Result<UniformBufferObject> {
+        let time = self.clock.time() as f32;
 
         let model = Mat4::from_axis_angle(vec3(0.0, 0.0, 1.0), Deg(45.0) * time);
 
-        let view = Mat4::look_at_rh(
-            point3(0.0, 2.5, 2.5),
-            point3(0.0, 0.0, 0.0),
-            vec3(0.0, 0.0, 1.0),
-        );
+        let view = Mat4::look_at_rh(self.camera_eye, point3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0));
 
-        // Mat4::new constructs the matrix in a column-major order, so the matrix look like
-        // [1,  0, 0  , 0  ]
-        // [0, -1, 0  , 0  ]
-        // [0,  0, 0.5, 0.5]
-        // [0,  0, 0  , 1  ]
-        let correction = Mat4::new(
-            1.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -1.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            1.0 / 2.0,
-            0.0,
-            0.0,
-            0.0,
-            1.0 / 2.0,
-            1.0,
-        );
-
-        // cgmath was originally designed for OpenGL, where the Y coordinate of the clip coordinates
-        // is inverted. This is the easiest way to compensate it.
-        let proj = correction
-            * cgmath::perspective(
-                Deg(45.0),
-                self.data.swapchain_extent.width as f32 / self.data.swapchain_extent.height as f32,
-                2.5,
-                4.0,
-            );
+        let proj = projection(self.data.swapchain_extent);
 
         // Passing in individual matrices to the GPU and multiplying them in the vertex shader
         // offloads work to the GPU, but is not recommended for low-poly meshes.
@@ -495,10 +692,172 @@ impl App {
                 .unmap_memory(self.data.uniform_buffers_memory[image_index]);
         }
 
+        Ok(ubo)
+    }
+
+    /// Returns the GPU time of the last measured comparison in nanoseconds, or `None`
+    /// if profiling is disabled, unsupported or no frame has been measured yet.
+    pub fn comparator_duration_ns(&self) -> Option<f64> {
+        self.data.comparator_duration_ns
+    }
+
+    /// Reads the comparator output of a rendered image back to host memory as 8-bit sRGB
+    /// RGBA. The image must not have been acquired again since, which is the case right
+    /// after `render_headless` returned its index.
+    pub fn read_output(&mut self, image_index: usize) -> Result<Vec<u8>> {
+        read_output(&self.instance, &self.device, &mut self.data, image_index)
+    }
+
+    /// Saves the comparator output of the next rendered frame as a PNG file
+    /// in the working directory.
+    pub fn request_screenshot(&mut self) {
+        self.capture_request.output = true;
+    }
+
+    /// Saves every comparator input of the next rendered frame as a separate PNG file
+    /// in the working directory.
+    pub fn request_input_capture(&mut self) {
+        self.capture_request.inputs = true;
+    }
+
+    /// Saves the raw values of the resolved depth buffer of the next rendered frame,
+    /// together with the planes and the projection they were rendered with.
+    pub fn request_depth_capture(&mut self) {
+        self.capture_request.depth = true;
+    }
+
+    pub fn cycle_capture_format(&mut self) {
+        self.capture_format = self.capture_format.next();
+        info!("Capturing images as {:?}.", self.capture_format);
+    }
+
+    /// Stops or resumes the animation.
+    pub fn toggle_pause(&mut self) {
+        self.clock.toggle_pause();
+        info!(
+            "Animation {} at {:.3} s.",
+            if self.clock.is_paused() {
+                "paused"
+            } else {
+                "resumed"
+            },
+            self.clock.time()
+        );
+    }
+
+    /// Advances the paused animation by a single frame.
+    pub fn step_frame(&mut self) {
+        self.clock.step();
+    }
+
+    /// Switches from the wall clock to a fixed step per frame, from there to an explicit
+    /// time that stays where it is, and back to the wall clock. The step is the configured
+    /// one, if any.
+    pub fn cycle_clock_mode(&mut self) {
+        let step = match self.data.config.clock_mode {
+            Some(ClockMode::FixedStep { step }) => step,
+            _ => DEFAULT_STEP,
+        };
+        let mode = match self.clock.mode() {
+            ClockMode::WallClock => ClockMode::FixedStep { step },
+            ClockMode::FixedStep { .. } => ClockMode::Explicit,
+            ClockMode::Explicit => ClockMode::WallClock,
+        };
+        self.clock.set_mode(mode);
+        info!("Animating with {:?} from {:.3} s.", mode, self.clock.time());
+    }
+
+    /// Sets the animation time, e.g. to go back to the start.
+    pub fn set_time(&mut self, time: f64) {
+        self.clock.set_time(time);
+        info!("Animation time set to {:.3} s.", time);
+    }
+
+    /// Starts appending the metrics of every frame to the given file.
+    pub fn start_metrics_log(
+        &mut self,
+        path: impl Into<PathBuf>,
+        format: MetricsFormat,
+    ) -> Result<()> {
+        self.metrics_log = Some(MetricsLog::create(path, format)?);
+        Ok(())
+    }
+
+    pub fn stop_metrics_log(&mut self) -> Result<()> {
+        if let Some(mut log) = self.metrics_log.take() {
+            log.flush()?;
+            info!("Frame metrics written to {}.", log.path().display());
+        }
+        Ok(())
+    }
+
+    /// Starts logging the frame metrics to a timestamped file in the working directory,
+    /// or stops logging if it's already running.
+    pub fn toggle_metrics_log(&mut self, format: MetricsFormat) -> Result<()> {
+        if self.metrics_log.is_some() {
+            return self.stop_metrics_log();
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.start_metrics_log(
+            format!("metrics_{timestamp}.{}", format.extension()),
+            format,
+        )
+    }
+
+    /// Starts saving every `interval`-th presented frame to a numbered PNG sequence
+    /// in the given directory.
+    pub fn start_recording(&mut self, directory: impl Into<PathBuf>, interval: u64) -> Result<()> {
+        self.stop_recording()?;
+        self.recorder = Some(Recorder::start(directory, interval)?);
         Ok(())
     }
 
-    pub(crate) fn update(&mut self, window: &Window, mouse_x: f64, mouse_left_pressed: bool) {
+    /// Stops the recording once the frames still in flight have been written.
+    pub fn stop_recording(&mut self) -> Result<()> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.stop(&self.device, self.data.command_pool),
+            None => Ok(()),
+        }
+    }
+
+    /// Starts recording to a new directory named by the current time or stops the
+    /// current recording.
+    pub fn toggle_recording(&mut self, interval: u64) -> Result<()> {
+        if self.recorder.is_some() {
+            return self.stop_recording();
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.start_recording(format!("recording_{timestamp}"), interval)
+    }
+
+    /// The metrics computed for the current frame. The GPU timings are read back with
+    /// a delay of a few frames, once the command buffer that measured them has finished.
+    fn frame_metrics(&self) -> Vec<(&'static str, Option<f64>)> {
+        vec![("comparator_gpu_ns", self.data.comparator_duration_ns)]
+    }
+
+    fn log_frame_metrics(&mut self) -> Result<()> {
+        if self.metrics_log.is_none() {
+            return Ok(());
+        }
+
+        let record = MetricsRecord {
+            frame: self.frame_count,
+            elapsed: self.start.elapsed().as_secs_f64(),
+            divider_position: self.data.vbar_percentage,
+            metrics: self.frame_metrics(),
+        };
+
+        if let Some(log) = &mut self.metrics_log {
+            log.append(&record)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn update(&mut self, window: &Window, mouse_x: f64, mouse_left_pressed: bool) {
         let window_size = window.inner_size();
         let margin = 10.0;
         /* let mouse_in_viewport = mouse_x > (window_size.width as f64 / 10f64 * 7f64)
@@ -520,16 +879,23 @@ impl App {
 /// The Vulkan handles and associated properties used by our Vulkan app.
 #[derive(Default)]
 pub struct AppData {
+    /// The settings from the command line.
+    pub config: Config,
     pub messenger: vk::DebugUtilsMessengerEXT,
     pub physical_device: vk::PhysicalDevice,
+    /// The name, driver version etc. of the selected physical device.
+    pub physical_device_properties: vk::PhysicalDeviceProperties,
     pub msaa_samples: vk::SampleCountFlags,
     pub graphics_queue: vk::Queue,
     pub present_queue: vk::Queue,
     pub surface: vk::SurfaceKHR,
     pub swapchain_format: vk::Format,
     pub swapchain_extent: vk::Extent2D,
+    pub swapchain_usage: vk::ImageUsageFlags,
     pub swapchain: vk::SwapchainKHR,
     pub swapchain_images: Vec<vk::Image>,
+    /// The memory of the images that replace the swapchain images in headless mode.
+    pub offscreen_images_memory: Vec<vk::DeviceMemory>,
     pub swapchain_image_views: Vec<vk::ImageView>,
     // 0 for the left side, 1 for the right side
     pub render_pass: vk::RenderPass,
@@ -617,4 +983,11 @@ pub struct AppData {
     // Frame comparator
     pub frame_comparators: Option<Vec<RenderTargetComparator>>,
     pub frame_comp_viewport: vk::Viewport,
+
+    /// Timestamps written around the frame comparator's commands, null if unsupported.
+    pub comparator_query_pool: vk::QueryPool,
+    /// Nanoseconds per timestamp tick.
+    pub timestamp_period: f32,
+    pub timestamp_mask: u64,
+    pub comparator_duration_ns: Option<f64>,
 }
@@ -0,0 +1,199 @@
+//! Compares two PNG files without launching the interactive app.
+//!
+//! The images are composed by the frame comparator on a headless Vulkan device, or on the
+//! CPU if there is none, and the composed image and the metrics are written to disk.
+//!
+//! Given two directories instead, every pair of PNGs with the same name is compared and
+//! the results are summarized in an HTML report.
+
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use frame_comp_app::config::parse_unit;
+use frame_comp_app::metrics::compute_metrics;
+use frame_comp_app::offline::{GpuComparator, Image, compose_on_cpu, metrics_json};
+use frame_comp_app::report::{ReportEntry, compare_directories, html_report};
+use log::{info, warn};
+
+const USAGE: &str = "\
+Usage: compare_images <a.png> <b.png> [options]
+       compare_images <a_dir> <b_dir> [--report <file.html>] [--tolerance <0..1>]
+
+Options:
+  --output <file.png>    The composed comparison image [default: comparison.png]
+  --metrics <file.json>  The metrics of the comparison [default: comparison.json]
+  --divider <0..1>       The position of the split between the images [default: 0.5]
+  --tolerance <0..1>     Differences up to this value don't count as changed pixels [default: 0]
+  --cpu                  Compose the images on the CPU, without a Vulkan device
+  --report <file.html>   The report of a comparison of two directories [default: report.html]
+  -h, --help             Print this help";
+
+struct Options {
+    a: PathBuf,
+    b: PathBuf,
+    output: PathBuf,
+    metrics: PathBuf,
+    report: PathBuf,
+    divider: f32,
+    tolerance: f32,
+    cpu: bool,
+    /// The options that were given, to reject those that don't apply to the inputs.
+    given: Vec<String>,
+}
+
+impl Options {
+    /// Returns `None` if the help was requested.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>> {
+        let mut inputs = vec![];
+        let mut options = Self {
+            a: PathBuf::new(),
+            b: PathBuf::new(),
+            output: "comparison.png".into(),
+            metrics: "comparison.json".into(),
+            report: "report.html".into(),
+            divider: 0.5,
+            tolerance: 0.0,
+            cpu: false,
+            given: vec![],
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("Missing the value of {arg}."))
+            };
+
+            if arg.starts_with("--") {
+                options.given.push(arg.clone());
+            }
+
+            match arg.as_str() {
+                "--output" => options.output = value()?.into(),
+                "--metrics" => options.metrics = value()?.into(),
+                "--report" => options.report = value()?.into(),
+                "--divider" => options.divider = parse_unit(&arg, &value()?)?,
+                "--tolerance" => options.tolerance = parse_unit(&arg, &value()?)?,
+                "--cpu" => options.cpu = true,
+                "--help" | "-h" => return Ok(None),
+                _ if arg.starts_with("--") => {
+                    return Err(anyhow!("Unknown option {arg}.\n\n{USAGE}"));
+                }
+                _ => inputs.push(PathBuf::from(arg)),
+            }
+        }
+
+        let [a, b] = <[PathBuf; 2]>::try_from(inputs)
+            .map_err(|_| anyhow!("Expected exactly two images.\n\n{USAGE}"))?;
+        options.a = a;
+        options.b = b;
+
+        Ok(Some(options))
+    }
+}
+
+fn main() -> Result<()> {
+    pretty_env_logger::init();
+
+    let Some(options) = Options::parse(std::env::args().skip(1))? else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+    if options.a.is_dir() && options.b.is_dir() {
+        reject_options(
+            &options,
+            &["--output", "--metrics", "--divider", "--cpu"],
+            "directories",
+        )?;
+        return compare_batch(&options);
+    }
+    reject_options(&options, &["--report"], "images")?;
+
+    let a = Image::read(&options.a)?;
+    let b = Image::read(&options.b)?;
+
+    let gpu = if options.cpu {
+        None
+    } else {
+        GpuComparator::create()
+            .inspect_err(|e| warn!("No Vulkan device, composing on the CPU: {:?}", e))
+            .ok()
+    };
+
+    let (composed, composed_on) = match gpu {
+        Some(mut gpu) => {
+            let result = gpu.compose(&a, &b, options.divider);
+            let device_name = gpu.device_name();
+            gpu.destroy();
+            (result?, device_name)
+        }
+        None => (compose_on_cpu(&a, &b, options.divider)?, "cpu".to_string()),
+    };
+
+    composed.write(&options.output)?;
+    info!("Wrote {}.", options.output.display());
+
+    let metrics = compute_metrics(&a.as_rgba()?, &b.as_rgba()?, options.tolerance)?;
+    std::fs::write(&options.metrics, metrics_json(&metrics, &composed_on))?;
+    info!("Wrote {}.", options.metrics.display());
+
+    println!(
+        "PSNR {:.2} dB, SSIM {:.4}, {} changed pixels",
+        metrics.psnr.overall, metrics.ssim, metrics.changed_pixels
+    );
+
+    Ok(())
+}
+
+/// Fails if any of the given options don't apply when comparing the kind of inputs.
+fn reject_options(options: &Options, names: &[&str], inputs: &str) -> Result<()> {
+    let rejected = options
+        .given
+        .iter()
+        .filter(|o| names.contains(&o.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if rejected.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} can't be used when comparing two {inputs}.\n\n{USAGE}",
+            rejected.join(", ")
+        ))
+    }
+}
+
+/// Compares the images of two directories and writes the HTML report.
+fn compare_batch(options: &Options) -> Result<()> {
+    let entries = compare_directories(&options.a, &options.b, options.tolerance)?;
+
+    let report = html_report(&options.a, &options.b, &entries)?;
+    std::fs::write(&options.report, report)?;
+    info!("Wrote {}.", options.report.display());
+
+    // The entries are sorted worst first.
+    let (mut changed, mut failed) = (0, 0);
+    for entry in &entries {
+        match entry {
+            ReportEntry::Compared { name, metrics, .. } if metrics.changed_pixels > 0 => {
+                changed += 1;
+                println!(
+                    "{name}: PSNR {:.2} dB, SSIM {:.4}, {} changed pixels",
+                    metrics.psnr.overall, metrics.ssim, metrics.changed_pixels
+                );
+            }
+            ReportEntry::Compared { .. } => {}
+            ReportEntry::Failed { name, reason } => {
+                failed += 1;
+                println!("{name}: {reason}");
+            }
+        }
+    }
+
+    println!(
+        "{} images, {changed} changed, {failed} failed",
+        entries.len()
+    );
+
+    Ok(())
+}
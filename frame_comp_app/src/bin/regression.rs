@@ -0,0 +1,272 @@
+//! Golden-image regression tests of the renderer.
+//!
+//! Renders the scene headless at fixed camera poses and a fixed animation time and
+//! compares each comparator output with a golden PNG stored in the repository. A case
+//! fails when its PSNR or SSIM falls below the threshold; the actual image and the
+//! difference are then written to the output directory for inspection.
+//!
+//! After an intended change of the rendering, `--bless` replaces the goldens with the
+//! current output. A case without a golden image fails, so a deleted golden doesn't go
+//! unnoticed, until `--bless` creates it.
+
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use frame_comp_app::app::App;
+use frame_comp_app::clock::Clock;
+use frame_comp_app::config::{Config, DEFAULT_MODEL_PATH, DEFAULT_TEXTURE_PATH};
+use frame_comp_app::metrics::{CompareMode, compose, compute_metrics};
+use frame_comp_app::offline::Image;
+use log::info;
+use vulkanalia::vk;
+
+const USAGE: &str = "\
+Usage: regression [options]
+
+Options:
+  --bless               Replace the golden images with the current output
+  --model <file.obj>    The model to render [default: frame_comp_app/resources/viking_room.obj]
+  --texture <file.png>  The texture of the model [default: frame_comp_app/resources/viking_room.png]
+  --golden-dir <dir>    The golden images [default: frame_comp_app/tests/golden]
+  --output-dir <dir>    Where the images of failed cases go [default: regression_output]
+  --min-psnr <dB>       The lowest PSNR that passes [default: 40]
+  --min-ssim <0..1>     The lowest SSIM that passes [default: 0.99]";
+
+/// The size of the rendered images, kept small so the goldens don't bloat the repository.
+const EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 640,
+    height: 384,
+};
+
+/// A camera pose and animation time to render the scene at.
+struct Case {
+    name: &'static str,
+    camera_eye: [f32; 3],
+    /// Seconds of the model animation.
+    time: f64,
+}
+
+// The eyes stay between the near (2.5) and far (4.0) planes' distance from the model.
+const CASES: &[Case] = &[
+    Case {
+        name: "front",
+        camera_eye: [0.0, 2.5, 2.5],
+        time: 0.0,
+    },
+    Case {
+        name: "front_rotated",
+        camera_eye: [0.0, 2.5, 2.5],
+        time: 1.0,
+    },
+    Case {
+        name: "side",
+        camera_eye: [2.5, 0.0, 2.5],
+        time: 0.0,
+    },
+    Case {
+        name: "diagonal",
+        camera_eye: [1.5, 1.5, 2.5],
+        time: 2.5,
+    },
+];
+
+struct Options {
+    bless: bool,
+    model_path: PathBuf,
+    texture_path: PathBuf,
+    golden_dir: PathBuf,
+    output_dir: PathBuf,
+    min_psnr: f64,
+    min_ssim: f64,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut options = Self {
+            bless: false,
+            // The assets checked into the repository, so the goldens render the same scene
+            // on every machine.
+            model_path: DEFAULT_MODEL_PATH.into(),
+            texture_path: DEFAULT_TEXTURE_PATH.into(),
+            golden_dir: concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden").into(),
+            output_dir: "regression_output".into(),
+            min_psnr: 40.0,
+            min_ssim: 0.99,
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("Missing the value of {arg}."))
+            };
+
+            match arg.as_str() {
+                "--bless" => options.bless = true,
+                "--model" => options.model_path = value()?.into(),
+                "--texture" => options.texture_path = value()?.into(),
+                "--golden-dir" => options.golden_dir = value()?.into(),
+                "--output-dir" => options.output_dir = value()?.into(),
+                "--min-psnr" => options.min_psnr = parse_number(&arg, &value()?)?,
+                "--min-ssim" => options.min_ssim = parse_number(&arg, &value()?)?,
+                _ => return Err(anyhow!("Unknown argument {arg}.\n\n{USAGE}")),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_number(name: &str, value: &str) -> Result<f64> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("{name} must be a number, got '{value}'."))
+}
+
+/// Renders a case and returns the comparator output.
+fn render(app: &mut App, case: &Case) -> Result<Image> {
+    app.camera_eye = case.camera_eye.into();
+    app.clock = Clock::at(case.time);
+
+    let image_index = app.render_headless()?;
+
+    Ok(Image {
+        width: EXTENT.width,
+        height: EXTENT.height,
+        pixels: app.read_output(image_index)?,
+    })
+}
+
+/// The result of checking a case against its golden image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Outcome {
+    Passed,
+    Failed,
+    /// There is no golden image to compare with, which fails the case too.
+    Missing,
+}
+
+/// Compares a case with its golden image.
+fn check(options: &Options, case: &Case, actual: &Image) -> Result<Outcome> {
+    let golden_path = options.golden_dir.join(format!("{}.png", case.name));
+    if !golden_path.exists() {
+        println!(
+            "FAIL {}: there is no golden image {}",
+            case.name,
+            golden_path.display()
+        );
+        return Ok(Outcome::Missing);
+    }
+
+    let golden = Image::read(&golden_path)?;
+    if (golden.width, golden.height) != (actual.width, actual.height) {
+        println!(
+            "FAIL {}: the golden image is {}x{}, the output {}x{}",
+            case.name, golden.width, golden.height, actual.width, actual.height
+        );
+        return Ok(Outcome::Failed);
+    }
+
+    let (golden_rgba, actual_rgba) = (golden.as_rgba()?, actual.as_rgba()?);
+    let metrics = compute_metrics(&golden_rgba, &actual_rgba, 0.0)?;
+    let passed = metrics.psnr.overall >= options.min_psnr && metrics.ssim >= options.min_ssim;
+
+    println!(
+        "{} {}: PSNR {:.2} dB, SSIM {:.4}, {} changed pixels",
+        if passed { "ok  " } else { "FAIL" },
+        case.name,
+        metrics.psnr.overall,
+        metrics.ssim,
+        metrics.changed_pixels
+    );
+
+    if !passed {
+        std::fs::create_dir_all(&options.output_dir)?;
+
+        let actual_path = options.output_dir.join(format!("{}_actual.png", case.name));
+        actual.write(&actual_path)?;
+
+        let difference = Image {
+            width: actual.width,
+            height: actual.height,
+            pixels: compose(&golden_rgba, &actual_rgba, CompareMode::Difference)?,
+        };
+        let difference_path = options.output_dir.join(format!("{}_diff.png", case.name));
+        difference.write(&difference_path)?;
+
+        info!(
+            "Wrote {} and {}.",
+            actual_path.display(),
+            difference_path.display()
+        );
+    }
+
+    Ok(if passed {
+        Outcome::Passed
+    } else {
+        Outcome::Failed
+    })
+}
+
+/// Renders and checks (or blesses) every case and returns the outcome of each.
+fn run(app: &mut App, options: &Options) -> Result<Vec<Outcome>> {
+    if options.bless {
+        std::fs::create_dir_all(&options.golden_dir)?;
+    }
+
+    let mut outcomes = vec![];
+    for case in CASES {
+        let actual = render(app, case)?;
+
+        if options.bless {
+            let path = options.golden_dir.join(format!("{}.png", case.name));
+            actual.write(&path)?;
+            println!("blessed {}", path.display());
+            outcomes.push(Outcome::Passed);
+        } else {
+            outcomes.push(check(options, case, &actual)?);
+        }
+    }
+
+    Ok(outcomes)
+}
+
+fn main() -> Result<()> {
+    pretty_env_logger::init();
+
+    let options = Options::parse(std::env::args().skip(1))?;
+
+    // Everything that affects the output is pinned, so the goldens don't depend on the
+    // device or the build. Every device supports 4 samples.
+    let mut app = App::create_headless(Config {
+        width: EXTENT.width,
+        height: EXTENT.height,
+        model_path: options.model_path.clone(),
+        texture_path: options.texture_path.clone(),
+        samples: Some(vk::SampleCountFlags::_4),
+        frames_in_flight: 1,
+        validation: false,
+        divider_position: 0.5,
+        ..Config::default()
+    })?;
+    let result = run(&mut app, &options);
+    app.destroy();
+
+    let outcomes = result?;
+    let count = |outcome| outcomes.iter().filter(|o| **o == outcome).count();
+    let (failed, missing) = (count(Outcome::Failed), count(Outcome::Missing));
+
+    if missing > 0 {
+        println!(
+            "{missing} of {} cases have no golden image, run with --bless to create them.",
+            CASES.len()
+        );
+    }
+
+    match failed + missing {
+        0 => Ok(()),
+        failed => Err(anyhow!(
+            "{failed} of {} regression cases failed.",
+            CASES.len()
+        )),
+    }
+}
@@ -0,0 +1,437 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Result, anyhow};
+use log::info;
+use vulkanalia::prelude::v1_3::*;
+
+use crate::app::AppData;
+use crate::comparator::{comparator_inputs, output_layout};
+use crate::image_file::{
+    is_float, is_srgb, to_depth_f32, to_rgba_f32, to_rgba8, write_pfm, write_pfm_gray, write_png,
+    write_png16, write_png16_gray,
+};
+use crate::metrics::Channel;
+use crate::metrics_log::{json_number, json_string};
+use crate::vulkan::buffers::depth_buffer::get_depth_format;
+use crate::vulkan::buffers::uniform_buffer::{
+    FAR_PLANE, FIELD_OF_VIEW, Mat4, NEAR_PLANE, UniformBufferObject,
+};
+use crate::vulkan::commands::{begin_single_time_commands, end_single_time_commands};
+use crate::vulkan::readback::ReadbackBuffer;
+
+/// The images to capture from the next rendered frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CaptureRequest {
+    /// The comparator output (the swapchain image).
+    pub output: bool,
+    /// Every image compared by the comparator, each to its own file.
+    pub inputs: bool,
+    /// The raw values of the resolved depth buffer.
+    pub depth: bool,
+}
+
+impl CaptureRequest {
+    pub fn is_empty(&self) -> bool {
+        !self.output && !self.inputs && !self.depth
+    }
+}
+
+/// The file formats captured images can be written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// 8-bit sRGB PNG, like the images shown on screen.
+    #[default]
+    Png8,
+    /// 16-bit PNG with the values the comparator samples (linear for sRGB formats),
+    /// so small differences don't disappear in the 8-bit sRGB quantization.
+    Png16,
+    /// Portable float map with the exact 32-bit float values the comparator samples.
+    Pfm,
+}
+
+impl CaptureFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png8 | Self::Png16 => "png",
+            Self::Pfm => "pfm",
+        }
+    }
+
+    /// The next format, to cycle through all of them.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Png8 => Self::Png16,
+            Self::Png16 => Self::Pfm,
+            Self::Pfm => Self::Png8,
+        }
+    }
+}
+
+/// The state of the frame a capture is taken from, written to a JSON sidecar next to
+/// every captured image so the frame can be reproduced later.
+#[derive(Clone, Copy, Debug)]
+pub struct CaptureMetadata {
+    pub frame: u64,
+    /// The animation time of the frame in seconds, see `Clock`.
+    pub time: f64,
+    pub divider_position: f32,
+    /// The matrices the frame was rendered with.
+    pub ubo: UniformBufferObject,
+}
+
+/// A capture whose copy commands have been submitted together with a frame.
+/// The texels can be written to disk once the frame's fence is signaled.
+pub struct PendingCapture {
+    pub command_buffer: vk::CommandBuffer,
+    pub targets: Vec<CaptureTarget>,
+}
+
+/// An image copied into a readback buffer and the file it will be written to.
+pub struct CaptureTarget {
+    pub readback: ReadbackBuffer,
+    pub path: PathBuf,
+    pub format: CaptureFormat,
+    /// The aspect that was copied, COLOR or DEPTH.
+    pub aspect: vk::ImageAspectFlags,
+    /// The JSON sidecar, written next to the image with the same name and a `.json`
+    /// extension.
+    pub metadata: String,
+}
+
+impl PendingCapture {
+    /// Writes all the captured images to disk and frees the capture's resources.
+    /// The command buffer must have finished executing.
+    pub fn finish(self, device: &Device, command_pool: vk::CommandPool) -> Result<()> {
+        let result = self
+            .targets
+            .iter()
+            .try_for_each(|t| write_capture(device, t));
+
+        self.discard(device, command_pool);
+
+        result
+    }
+
+    /// Frees the capture's resources without writing anything.
+    pub fn discard(self, device: &Device, command_pool: vk::CommandPool) {
+        unsafe { device.free_command_buffers(command_pool, &[self.command_buffer]) };
+        self.targets.iter().for_each(|t| t.readback.destroy(device));
+    }
+
+    /// Records the copy of an image into a new readback buffer. The image is expected
+    /// in the given layout and is left in it afterwards.
+    fn add_target(
+        &mut self,
+        instance: &Instance,
+        device: &Device,
+        data: &mut AppData,
+        image: vk::Image,
+        format: vk::Format,
+        aspect: vk::ImageAspectFlags,
+        layout: vk::ImageLayout,
+        path: PathBuf,
+        capture_format: CaptureFormat,
+        metadata: String,
+    ) -> Result<()> {
+        let extent = data.swapchain_extent;
+        let readback = ReadbackBuffer::create(instance, device, data, extent, format)?;
+
+        // The copy waits for any earlier write to the image, be it by a render pass
+        // (including the depth resolve) or by the comparator.
+        readback.record_copy(
+            device,
+            self.command_buffer,
+            image,
+            layout,
+            aspect,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::AccessFlags::MEMORY_WRITE,
+        );
+
+        self.targets.push(CaptureTarget {
+            readback,
+            path,
+            format: capture_format,
+            aspect,
+            metadata,
+        });
+
+        Ok(())
+    }
+}
+
+/// Records a command buffer that copies the requested images of a frame into host
+/// visible memory. It has to be submitted right after the frame's command buffer,
+/// before the swapchain image is presented.
+///
+/// All the files of one capture share the same timestamp and frame number, so the
+/// comparator output and its inputs can be matched later.
+pub fn record_capture(
+    instance: &Instance,
+    device: &Device,
+    data: &mut AppData,
+    image_index: usize,
+    request: CaptureRequest,
+    format: CaptureFormat,
+    metadata: &CaptureMetadata,
+) -> Result<PendingCapture> {
+    let command_buffer = begin_single_time_commands(device, data)?;
+    let mut capture = PendingCapture {
+        command_buffer,
+        targets: vec![],
+    };
+
+    let result = record_targets(
+        instance,
+        device,
+        data,
+        &mut capture,
+        image_index,
+        request,
+        format,
+        metadata,
+    );
+
+    // Nothing is submitted if any of the copies couldn't be recorded.
+    match result.and_then(|_| Ok(unsafe { device.end_command_buffer(command_buffer) }?)) {
+        Ok(()) => Ok(capture),
+        Err(e) => {
+            capture.discard(device, data.command_pool);
+            Err(e)
+        }
+    }
+}
+
+fn record_targets(
+    instance: &Instance,
+    device: &Device,
+    data: &mut AppData,
+    capture: &mut PendingCapture,
+    image_index: usize,
+    request: CaptureRequest,
+    capture_format: CaptureFormat,
+    metadata: &CaptureMetadata,
+) -> Result<()> {
+    let name = capture_name(metadata.frame)?;
+    let extension = capture_format.extension();
+    let frame_fields = frame_metadata(data, metadata);
+    let sidecar = |image: &str, format: vk::Format, extra: &str| {
+        format!(
+            "{{\"image\":{},\"format\":\"{:?}\",\"file_format\":\"{:?}\",{frame_fields}{extra}}}\n",
+            json_string(image),
+            format,
+            capture_format,
+        )
+    };
+
+    if request.output {
+        if !data
+            .swapchain_usage
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+        {
+            return Err(anyhow!("The swapchain images can't be copied from."));
+        }
+
+        let image = data.swapchain_images[image_index];
+        let format = data.swapchain_format;
+        capture.add_target(
+            instance,
+            device,
+            data,
+            image,
+            format,
+            vk::ImageAspectFlags::COLOR,
+            output_layout(data),
+            format!("screenshot_{name}.{extension}").into(),
+            capture_format,
+            sidecar("output", format, ""),
+        )?;
+    }
+
+    if request.inputs {
+        // The render pass leaves the inputs ready to be sampled by the comparator.
+        for (i, (image, _)) in comparator_inputs(data).into_iter().enumerate() {
+            let format = data.swapchain_format;
+            capture.add_target(
+                instance,
+                device,
+                data,
+                image,
+                format,
+                vk::ImageAspectFlags::COLOR,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                format!("input{i}_{name}.{extension}").into(),
+                capture_format,
+                sidecar(&format!("input{i}"), format, ""),
+            )?;
+        }
+    }
+
+    if request.depth {
+        // An 8-bit PNG would lose most of the depth precision, so anything but PFM
+        // is written as a 16-bit PNG.
+        let extension = match capture_format {
+            CaptureFormat::Pfm => "pfm",
+            CaptureFormat::Png8 | CaptureFormat::Png16 => "png",
+        };
+
+        // The render pass leaves the resolved depth ready to be read as an input attachment.
+        let image = data.depth_res_image;
+        let format = get_depth_format(instance, data)?;
+        capture.add_target(
+            instance,
+            device,
+            data,
+            image,
+            format,
+            vk::ImageAspectFlags::DEPTH,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            format!("depth_{name}.{extension}").into(),
+            capture_format,
+            sidecar("depth", format, &depth_metadata()),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Copies the comparator output of a swapchain image to host memory as RGBA8 and waits
+/// for it. Unlike a capture, the copy is submitted on its own after the frame.
+pub fn read_output(
+    instance: &Instance,
+    device: &Device,
+    data: &mut AppData,
+    image_index: usize,
+) -> Result<Vec<u8>> {
+    let (extent, format) = (data.swapchain_extent, data.swapchain_format);
+    let readback = ReadbackBuffer::create(instance, device, data, extent, format)?;
+
+    let result = begin_single_time_commands(device, data).and_then(|command_buffer| {
+        readback.record_copy(
+            device,
+            command_buffer,
+            data.swapchain_images[image_index],
+            output_layout(data),
+            vk::ImageAspectFlags::COLOR,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::AccessFlags::MEMORY_WRITE,
+        );
+        end_single_time_commands(device, data, command_buffer)?;
+
+        to_rgba8(&readback.read(device)?, format)
+    });
+
+    readback.destroy(device);
+    result
+}
+
+fn write_capture(device: &Device, target: &CaptureTarget) -> Result<()> {
+    let readback = &target.readback;
+    let texels = readback.read(device)?;
+    let vk::Extent2D { width, height } = readback.extent;
+
+    fs::write(target.path.with_extension("json"), &target.metadata)?;
+
+    if target.aspect == vk::ImageAspectFlags::DEPTH {
+        let depth = to_depth_f32(&texels, readback.format)?;
+        match target.format {
+            CaptureFormat::Pfm => write_pfm_gray(&target.path, width, height, &depth)?,
+            CaptureFormat::Png8 | CaptureFormat::Png16 => {
+                let depth = depth
+                    .into_iter()
+                    .map(u16::from_normalized)
+                    .collect::<Vec<_>>();
+                write_png16_gray(&target.path, width, height, &depth)?;
+            }
+        }
+        info!("Captured {}.", target.path.display());
+        return Ok(());
+    }
+
+    match target.format {
+        CaptureFormat::Png8 => {
+            let rgba = to_rgba8(&texels, readback.format)?;
+            write_png(&target.path, width, height, &rgba)?;
+        }
+        CaptureFormat::Png16 => {
+            let rgba = to_rgba_f32(&texels, readback.format)?
+                .into_iter()
+                .map(u16::from_normalized)
+                .collect::<Vec<_>>();
+            let linear = is_srgb(readback.format) || is_float(readback.format);
+            write_png16(&target.path, width, height, &rgba, linear)?;
+        }
+        CaptureFormat::Pfm => {
+            let rgba = to_rgba_f32(&texels, readback.format)?;
+            write_pfm(&target.path, width, height, &rgba)?;
+        }
+    }
+    info!("Captured {}.", target.path.display());
+
+    Ok(())
+}
+
+/// The JSON fields describing the device, the swapchain and the state of the frame.
+fn frame_metadata(data: &AppData, metadata: &CaptureMetadata) -> String {
+    let properties = &data.physical_device_properties;
+    let extent = data.swapchain_extent;
+
+    format!(
+        concat!(
+            "\"frame\":{},\"time\":{},\"divider_position\":{},",
+            // The comparator only has the split view so far.
+            "\"comparator_mode\":\"split\",",
+            "\"device\":{{\"name\":{},\"vendor_id\":{},\"driver_version\":{}}},",
+            "\"msaa_samples\":{},\"swapchain_format\":\"{:?}\",\"extent\":[{},{}],",
+            "\"model\":{},\"view\":{},\"proj\":{}",
+        ),
+        metadata.frame,
+        json_number(metadata.time),
+        json_number(metadata.divider_position as f64),
+        json_string(&properties.device_name.to_string()),
+        properties.vendor_id,
+        // Vendor specific encoding, decode with the vendor ID.
+        properties.driver_version,
+        data.msaa_samples.bits(),
+        data.swapchain_format,
+        extent.width,
+        extent.height,
+        json_matrix(&metadata.ubo.model),
+        json_matrix(&metadata.ubo.view),
+        json_matrix(&metadata.ubo.proj),
+    )
+}
+
+/// Describes how the captured depth values were produced, so they can be converted
+/// back to view space distances: `z = near * far / (far - depth * (far - near))`.
+/// The projection is the `proj` matrix of the frame.
+fn depth_metadata() -> String {
+    format!(
+        ",\"near\":{},\"far\":{},\"field_of_view\":{}",
+        json_number(NEAR_PLANE as f64),
+        json_number(FAR_PLANE as f64),
+        json_number(FIELD_OF_VIEW as f64),
+    )
+}
+
+/// A matrix as a JSON array of its 16 values in column-major order.
+fn json_matrix(matrix: &Mat4) -> String {
+    let values: &[f32; 16] = matrix.as_ref();
+    let values = values
+        .iter()
+        .map(|v| json_number(*v as f64))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!("[{values}]")
+}
+
+/// Returns the common part of the file names of a capture: the current time (seconds
+/// since the Unix epoch) and the frame number, e.g. `1760000000_frame42`.
+pub fn capture_name(frame: u64) -> Result<String> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    Ok(format!("{timestamp}_frame{frame}"))
+}
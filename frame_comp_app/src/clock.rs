@@ -0,0 +1,242 @@
+//! The time that animates the scene.
+//!
+//! By default the animation follows the wall clock, so no two runs render the same frames.
+//! For comparisons across runs the clock can instead advance by a fixed step per frame,
+//! which makes the time a function of the frame index, or stay at a time set explicitly.
+//! In every mode the clock can be paused and then advanced a single frame at a time.
+
+use std::time::Instant;
+
+/// How the clock advances from one frame to the next.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClockMode {
+    /// The time that has passed in the real world.
+    WallClock,
+    /// The same number of seconds every frame, regardless of how long the frame took.
+    FixedStep { step: f64 },
+    /// The time doesn't advance on its own, only when it is set.
+    Explicit,
+}
+
+/// The step of the fixed-step mode when none is given, one frame at 60 Hz.
+pub const DEFAULT_STEP: f64 = 1.0 / 60.0;
+
+/// The source of the animation time, ticked once for every rendered frame.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    mode: ClockMode,
+    /// The time of the current frame in seconds.
+    time: f64,
+    /// The time at which the frame-indexed time of the fixed-step mode starts.
+    origin: f64,
+    /// The number of steps taken since `origin`.
+    steps: u64,
+    /// When the clock was last ticked, to measure the wall clock time between frames.
+    last_tick: Instant,
+    /// Whether `tick` has been called yet. The first frame is rendered at the start time.
+    ticked: bool,
+    paused: bool,
+    /// Whether the next tick advances the clock despite it being paused.
+    single_step: bool,
+}
+
+impl Clock {
+    /// Creates a clock that starts at time 0.
+    pub fn new(mode: ClockMode) -> Self {
+        Self {
+            mode,
+            time: 0.0,
+            origin: 0.0,
+            steps: 0,
+            last_tick: Instant::now(),
+            ticked: false,
+            paused: false,
+            single_step: false,
+        }
+    }
+
+    /// Creates a clock that stays at the given time until it is changed.
+    pub fn at(time: f64) -> Self {
+        let mut clock = Self::new(ClockMode::Explicit);
+        clock.set_time(time);
+        clock
+    }
+
+    pub fn mode(&self) -> ClockMode {
+        self.mode
+    }
+
+    /// Switches the mode, continuing from the current time.
+    pub fn set_mode(&mut self, mode: ClockMode) {
+        self.mode = mode;
+        self.set_time(self.time);
+    }
+
+    /// The time of the current frame in seconds.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Sets the time of the current frame. The fixed-step mode counts its steps from here.
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+        self.origin = time;
+        self.steps = 0;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.single_step = false;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.set_paused(!self.paused);
+    }
+
+    /// Advances a paused clock by a single frame on the next tick: one step in the
+    /// fixed-step mode, `DEFAULT_STEP` seconds in the wall clock mode.
+    pub fn step(&mut self) {
+        self.single_step = self.paused;
+    }
+
+    /// Advances the clock to the time of the next frame and returns it.
+    pub fn tick(&mut self) -> f64 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_tick).as_secs_f64();
+        self.last_tick = now;
+
+        // The first frame is rendered at the start time in every mode.
+        if !std::mem::replace(&mut self.ticked, true) {
+            return self.time;
+        }
+
+        let single_step = std::mem::take(&mut self.single_step);
+        if self.paused && !single_step {
+            return self.time;
+        }
+
+        match self.mode {
+            ClockMode::WallClock if single_step => self.time += DEFAULT_STEP,
+            ClockMode::WallClock => self.time += elapsed,
+            // Multiplying instead of adding up the steps gives every frame index the same
+            // time, however long the clock runs.
+            ClockMode::FixedStep { step } => {
+                self.steps += 1;
+                self.time = self.origin + self.steps as f64 * step;
+            }
+            ClockMode::Explicit => {}
+        }
+
+        self.time
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new(ClockMode::WallClock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_tick_returns_the_start_time() {
+        let mut clock = Clock::new(ClockMode::WallClock);
+        clock.set_time(3.0);
+        assert_eq!(clock.tick(), 3.0);
+
+        let mut clock = Clock::new(ClockMode::FixedStep { step: 0.5 });
+        assert_eq!(clock.tick(), 0.0);
+    }
+
+    #[test]
+    fn fixed_step_depends_only_on_the_frame_index() {
+        let mut clock = Clock::new(ClockMode::FixedStep { step: 0.1 });
+        clock.set_time(1.0);
+        clock.tick();
+
+        for frame in 1..=1000 {
+            assert_eq!(clock.tick(), 1.0 + frame as f64 * 0.1);
+        }
+    }
+
+    #[test]
+    fn wall_clock_never_goes_back() {
+        let mut clock = Clock::default();
+        let mut previous = clock.tick();
+        for _ in 0..10 {
+            let time = clock.tick();
+            assert!(time >= previous);
+            previous = time;
+        }
+    }
+
+    #[test]
+    fn explicit_time_stays_put() {
+        let mut clock = Clock::at(2.5);
+        assert_eq!(clock.mode(), ClockMode::Explicit);
+        assert_eq!(clock.tick(), 2.5);
+        assert_eq!(clock.tick(), 2.5);
+
+        clock.set_time(4.0);
+        assert_eq!(clock.tick(), 4.0);
+    }
+
+    #[test]
+    fn paused_clock_advances_one_step_at_a_time() {
+        let mut clock = Clock::new(ClockMode::FixedStep { step: 0.5 });
+        clock.tick();
+        clock.set_paused(true);
+        assert_eq!(clock.tick(), 0.0);
+        assert_eq!(clock.tick(), 0.0);
+
+        clock.step();
+        assert_eq!(clock.tick(), 0.5);
+        assert_eq!(clock.tick(), 0.5);
+
+        clock.toggle_pause();
+        assert!(!clock.is_paused());
+        assert_eq!(clock.tick(), 1.0);
+    }
+
+    #[test]
+    fn paused_wall_clock_steps_by_the_default_step() {
+        let mut clock = Clock::default();
+        clock.tick();
+        clock.set_paused(true);
+        let time = clock.tick();
+
+        clock.step();
+        assert_eq!(clock.tick(), time + DEFAULT_STEP);
+    }
+
+    #[test]
+    fn step_is_ignored_while_running() {
+        let mut clock = Clock::new(ClockMode::FixedStep { step: 1.0 });
+        clock.tick();
+        clock.step();
+        clock.set_paused(true);
+        assert_eq!(clock.tick(), 0.0);
+    }
+
+    #[test]
+    fn setting_the_time_restarts_the_steps() {
+        let mut clock = Clock::new(ClockMode::FixedStep { step: 0.25 });
+        clock.tick();
+        clock.tick();
+        clock.tick();
+        assert_eq!(clock.time(), 0.5);
+
+        clock.set_time(10.0);
+        assert_eq!(clock.tick(), 10.25);
+
+        clock.set_mode(ClockMode::FixedStep { step: 1.0 });
+        assert_eq!(clock.tick(), 11.25);
+    }
+}
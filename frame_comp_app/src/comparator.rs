@@ -6,6 +6,26 @@ use rtcmp::{RenderTargetComparator, RenderTargetComparatorCreateInfo};
 
 use crate::app::AppData;
 
+/// The images compared by the frame comparator, as (image, view) pairs:
+/// the resolved scene and the grayscale depth.
+pub fn comparator_inputs(data: &AppData) -> [(vk::Image, vk::ImageView); 2] {
+    [
+        (data.resolve_image, data.resolve_image_view),
+        (data.color_image[1], data.color_image_view[1]),
+    ]
+}
+
+/// The layout the comparators leave their output in. Without a surface the swapchain
+/// extension, which defines the present layout, isn't enabled, so the output is left
+/// ready to be copied instead.
+pub fn output_layout(data: &AppData) -> vk::ImageLayout {
+    if data.surface.is_null() {
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+    } else {
+        vk::ImageLayout::PRESENT_SRC_KHR
+    }
+}
+
 pub fn create_comparators(
     device: &Rc<Device>,
     data: &AppData,
@@ -32,9 +52,10 @@ pub fn create_comparators(
                 .descriptor_pool(data.descriptor_pool)
                 .format(data.swapchain_format)
                 .extent(data.swapchain_extent)
-                .in_image_views([data.resolve_image_view, data.color_image_view[1]])
+                .in_image_views(comparator_inputs(data).map(|(_, view)| view))
                 .viewport(viewport)
                 .out_image_view(*i)
+                .final_layout(output_layout(data))
                 .build()?;
 
             RenderTargetComparator::new(&info)
@@ -0,0 +1,348 @@
+//! The settings of the app that can be changed from the command line.
+//!
+//! Everything has a default, so the app runs without any arguments. Invalid values are
+//! reported before a window or a Vulkan instance is created.
+
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use vulkanalia::prelude::v1_3::*;
+
+use crate::app::{MAX_FRAMES_IN_FLIGHT, VALIDATION_ENABLED};
+use crate::clock::ClockMode;
+
+pub const USAGE: &str = "\
+Usage: frame_comp_app [options]
+
+Options:
+  --model <file.obj>        The model to render
+                            [default: frame_comp_app/resources/viking_room.obj]
+  --texture <file.png>      The texture of the model
+                            [default: frame_comp_app/resources/viking_room.png]
+  --size <width>x<height>   The size of the window, or of the images in headless mode
+                            [default: 1820x1090]
+  --samples <2..64>         The MSAA sample count, a power of two [default: the maximum]
+  --present-mode <mode>     fifo, fifo-relaxed, mailbox or immediate
+                            [default: mailbox if available, fifo otherwise]
+  --device <index|name>     The physical device, by its index or part of its name
+                            [default: the first suitable one]
+  --validation <on|off>     The Vulkan validation layers [default: on]
+  --frames-in-flight <n>    How many frames the CPU may be ahead of the GPU, 1 to 3 [default: 3]
+  --divider <0..1>          The start position of the divider between the render targets
+                            [default: 0.5]
+  --fixed-step <seconds>    Advance the animation by this step every frame
+                            [default: the wall clock, a 60 Hz step in headless mode]
+  --time <seconds>          Start the animation at this time and keep it there, unless
+                            --fixed-step is given too [default: 0]
+  --headless                Render a single frame without a window and capture it
+  -h, --help                Print this help";
+
+/// The model checked into the repository, so the app runs without any arguments.
+pub const DEFAULT_MODEL_PATH: &str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/resources/viking_room.obj");
+/// The texture of `DEFAULT_MODEL_PATH`.
+pub const DEFAULT_TEXTURE_PATH: &str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/resources/viking_room.png");
+
+/// Selects the physical device to render with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeviceSelector {
+    /// The position in the list of physical devices.
+    Index(usize),
+    /// A case-insensitive part of the device name.
+    Name(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub model_path: PathBuf,
+    pub texture_path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    /// The MSAA sample count, or `None` for the maximum the device supports.
+    pub samples: Option<vk::SampleCountFlags>,
+    /// The requested present mode, or `None` to prefer mailbox and fall back to FIFO.
+    pub present_mode: Option<vk::PresentModeKHR>,
+    /// The physical device, or `None` for the first suitable one.
+    pub device: Option<DeviceSelector>,
+    pub validation: bool,
+    /// At most `MAX_FRAMES_IN_FLIGHT`, which the per-frame resources are sized for.
+    pub frames_in_flight: usize,
+    /// The start position of the divider between the render targets, in [0, 1].
+    pub divider_position: f32,
+    /// How the animation time advances, or `None` for the wall clock in a window and a
+    /// fixed step in headless mode.
+    pub clock_mode: Option<ClockMode>,
+    /// The animation time of the first frame in seconds.
+    pub start_time: f64,
+    pub headless: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            model_path: DEFAULT_MODEL_PATH.into(),
+            texture_path: DEFAULT_TEXTURE_PATH.into(),
+            width: 1820,
+            height: 1090,
+            samples: None,
+            present_mode: None,
+            device: None,
+            validation: VALIDATION_ENABLED,
+            frames_in_flight: MAX_FRAMES_IN_FLIGHT,
+            divider_position: 0.5,
+            clock_mode: None,
+            start_time: 0.0,
+            headless: false,
+        }
+    }
+}
+
+impl Config {
+    /// Parses the command line arguments, without the program name. Returns `None` if
+    /// the help was requested, which the caller prints as `USAGE`.
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>> {
+        let mut config = Self::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("Missing the value of {arg}.\n\n{USAGE}"))
+            };
+
+            match arg.as_str() {
+                "--model" => config.model_path = value()?.into(),
+                "--texture" => config.texture_path = value()?.into(),
+                "--size" => (config.width, config.height) = parse_size(&value()?)?,
+                "--samples" => config.samples = Some(parse_samples(&value()?)?),
+                "--present-mode" => config.present_mode = Some(parse_present_mode(&value()?)?),
+                "--device" => config.device = Some(parse_device(&value()?)),
+                "--validation" => config.validation = parse_switch(&arg, &value()?)?,
+                "--frames-in-flight" => {
+                    config.frames_in_flight = parse_frames_in_flight(&value()?)?
+                }
+                "--divider" => config.divider_position = parse_unit(&arg, &value()?)?,
+                "--fixed-step" => {
+                    let step = parse_seconds(&arg, &value()?)?;
+                    if step <= 0.0 {
+                        return Err(anyhow!("{arg} must be positive, got {step}."));
+                    }
+                    config.clock_mode = Some(ClockMode::FixedStep { step });
+                }
+                "--time" => {
+                    config.start_time = parse_seconds(&arg, &value()?)?;
+                    // An explicit time only holds the animation if no step was requested.
+                    if config.clock_mode.is_none() {
+                        config.clock_mode = Some(ClockMode::Explicit);
+                    }
+                }
+                "--headless" => config.headless = true,
+                "--help" | "-h" => return Ok(None),
+                _ => return Err(anyhow!("Unknown argument {arg}.\n\n{USAGE}")),
+            }
+        }
+
+        Ok(Some(config))
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        vk::Extent2D {
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32)> {
+    value
+        .split_once('x')
+        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+        .filter(|(w, h)| *w > 0 && *h > 0)
+        .ok_or_else(|| anyhow!("--size must be a width and height like 1820x1090, got '{value}'."))
+}
+
+fn parse_samples(value: &str) -> Result<vk::SampleCountFlags> {
+    // The render pass always resolves its attachments, which requires multisampling.
+    match value {
+        "2" => Ok(vk::SampleCountFlags::_2),
+        "4" => Ok(vk::SampleCountFlags::_4),
+        "8" => Ok(vk::SampleCountFlags::_8),
+        "16" => Ok(vk::SampleCountFlags::_16),
+        "32" => Ok(vk::SampleCountFlags::_32),
+        "64" => Ok(vk::SampleCountFlags::_64),
+        _ => Err(anyhow!(
+            "--samples must be 2, 4, 8, 16, 32 or 64, got '{value}'."
+        )),
+    }
+}
+
+fn parse_present_mode(value: &str) -> Result<vk::PresentModeKHR> {
+    match value {
+        "fifo" => Ok(vk::PresentModeKHR::FIFO),
+        "fifo-relaxed" => Ok(vk::PresentModeKHR::FIFO_RELAXED),
+        "mailbox" => Ok(vk::PresentModeKHR::MAILBOX),
+        "immediate" => Ok(vk::PresentModeKHR::IMMEDIATE),
+        _ => Err(anyhow!(
+            "--present-mode must be fifo, fifo-relaxed, mailbox or immediate, got '{value}'."
+        )),
+    }
+}
+
+fn parse_device(value: &str) -> DeviceSelector {
+    match value.parse() {
+        Ok(index) => DeviceSelector::Index(index),
+        Err(_) => DeviceSelector::Name(value.to_string()),
+    }
+}
+
+fn parse_switch(name: &str, value: &str) -> Result<bool> {
+    match value {
+        "on" | "true" | "1" => Ok(true),
+        "off" | "false" | "0" => Ok(false),
+        _ => Err(anyhow!("{name} must be on or off, got '{value}'.")),
+    }
+}
+
+fn parse_frames_in_flight(value: &str) -> Result<usize> {
+    value
+        .parse()
+        .ok()
+        .filter(|n| (1..=MAX_FRAMES_IN_FLIGHT).contains(n))
+        .ok_or_else(|| {
+            anyhow!(
+                "--frames-in-flight must be between 1 and {MAX_FRAMES_IN_FLIGHT}, got '{value}'."
+            )
+        })
+}
+
+/// Parses a value in the range [0, 1], e.g. a divider position.
+pub fn parse_unit(name: &str, value: &str) -> Result<f32> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|v| (0.0..=1.0).contains(v))
+        .ok_or_else(|| anyhow!("{name} must be a number between 0 and 1, got '{value}'."))
+}
+
+fn parse_seconds(name: &str, value: &str) -> Result<f64> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| anyhow!("{name} must be a number of seconds, got '{value}'."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Config>> {
+        Config::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_give_the_defaults() {
+        let config = parse(&[]).unwrap().unwrap();
+        assert_eq!(config.model_path, PathBuf::from(DEFAULT_MODEL_PATH));
+        assert!(config.model_path.is_file());
+        assert!(config.texture_path.is_file());
+        assert_eq!((config.width, config.height), (1820, 1090));
+        assert_eq!(config.samples, None);
+        assert_eq!(config.present_mode, None);
+        assert_eq!(config.device, None);
+        assert_eq!(config.frames_in_flight, MAX_FRAMES_IN_FLIGHT);
+        assert_eq!(config.divider_position, 0.5);
+        assert_eq!(config.clock_mode, None);
+        assert_eq!(config.start_time, 0.0);
+        assert!(!config.headless);
+    }
+
+    #[test]
+    fn valid_arguments() {
+        let config = parse(&[
+            "--model",
+            "model.obj",
+            "--size",
+            "640x480",
+            "--samples",
+            "4",
+            "--present-mode",
+            "fifo",
+            "--device",
+            "1",
+            "--validation",
+            "off",
+            "--frames-in-flight",
+            "2",
+            "--divider",
+            "0.25",
+            "--headless",
+        ])
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(config.model_path, PathBuf::from("model.obj"));
+        assert_eq!(config.extent().width, 640);
+        assert_eq!(config.extent().height, 480);
+        assert_eq!(config.samples, Some(vk::SampleCountFlags::_4));
+        assert_eq!(config.present_mode, Some(vk::PresentModeKHR::FIFO));
+        assert_eq!(config.device, Some(DeviceSelector::Index(1)));
+        assert!(!config.validation);
+        assert_eq!(config.frames_in_flight, 2);
+        assert_eq!(config.divider_position, 0.25);
+        assert!(config.headless);
+    }
+
+    #[test]
+    fn device_by_name() {
+        let config = parse(&["--device", "GeForce"]).unwrap().unwrap();
+        assert_eq!(config.device, Some(DeviceSelector::Name("GeForce".into())));
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        for args in [
+            &["--size", "0x1"][..],
+            &["--size", "640"],
+            &["--samples", "3"],
+            &["--samples", "1"],
+            &["--present-mode", "vsync"],
+            &["--validation", "maybe"],
+            &["--frames-in-flight", "0"],
+            &["--frames-in-flight", "4"],
+            &["--divider", "2"],
+            &["--divider", "-0.5"],
+            &["--fixed-step", "0"],
+            &["--time", "inf"],
+            &["--unknown"],
+            &["--size"],
+        ] {
+            assert!(parse(args).is_err(), "{args:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn help_is_not_an_error() {
+        assert!(parse(&["--help"]).unwrap().is_none());
+        assert!(parse(&["--size", "640x480", "-h"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn time_alone_holds_the_animation() {
+        let config = parse(&["--time", "1"]).unwrap().unwrap();
+        assert_eq!(config.clock_mode, Some(ClockMode::Explicit));
+        assert_eq!(config.start_time, 1.0);
+    }
+
+    #[test]
+    fn time_with_fixed_step_starts_the_steps() {
+        for args in [
+            ["--time", "2", "--fixed-step", "0.5"],
+            ["--fixed-step", "0.5", "--time", "2"],
+        ] {
+            let config = parse(&args).unwrap().unwrap();
+            assert_eq!(config.clock_mode, Some(ClockMode::FixedStep { step: 0.5 }));
+            assert_eq!(config.start_time, 2.0);
+        }
+    }
+}
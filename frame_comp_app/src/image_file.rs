@@ -0,0 +1,332 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use vulkanalia::prelude::v1_3::*;
+
+use crate::metrics::{Channel, linear_to_srgb, srgb_to_linear};
+
+/// Whether the values of the format are sRGB encoded in memory and decoded to linear
+/// when sampled.
+pub fn is_srgb(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::B8G8R8A8_SRGB | vk::Format::R8G8B8A8_SRGB
+    )
+}
+
+/// Whether the format stores floating-point values.
+pub fn is_float(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::R16G16B16A16_SFLOAT | vk::Format::R32G32B32A32_SFLOAT
+    )
+}
+
+/// Converts the texels read back from an image to tightly packed RGBA8.
+///
+/// The values of 8-bit sRGB formats are stored sRGB encoded, so they are copied as they are.
+/// The same applies to UNORM swapchain formats, whose values are presented unchanged.
+/// Values of other formats are treated as linear and sRGB encoded.
+pub fn to_rgba8(texels: &[u8], format: vk::Format) -> Result<Vec<u8>> {
+    match format {
+        vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => Ok(texels.to_vec()),
+        vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => Ok(texels
+            .chunks_exact(4)
+            .flat_map(|t| [t[2], t[1], t[0], t[3]])
+            .collect()),
+        _ => Ok(to_rgba_f32(texels, format)?
+            .chunks_exact(4)
+            .flat_map(|t| {
+                [
+                    u8::from_normalized(linear_to_srgb(t[0])),
+                    u8::from_normalized(linear_to_srgb(t[1])),
+                    u8::from_normalized(linear_to_srgb(t[2])),
+                    u8::from_normalized(t[3]),
+                ]
+            })
+            .collect()),
+    }
+}
+
+/// Converts the texels read back from an image to RGBA floats, exactly as a shader
+/// samples them: normalized, and decoded to linear for sRGB formats.
+pub fn to_rgba_f32(texels: &[u8], format: vk::Format) -> Result<Vec<f32>> {
+    let decode = |c: u8| {
+        let value = c.to_normalized();
+        if is_srgb(format) {
+            srgb_to_linear(value)
+        } else {
+            value
+        }
+    };
+
+    let rgba = match format {
+        vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => texels
+            .chunks_exact(4)
+            .flat_map(|t| {
+                [
+                    decode(t[0]),
+                    decode(t[1]),
+                    decode(t[2]),
+                    t[3].to_normalized(),
+                ]
+            })
+            .collect(),
+        vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => texels
+            .chunks_exact(4)
+            .flat_map(|t| {
+                [
+                    decode(t[2]),
+                    decode(t[1]),
+                    decode(t[0]),
+                    t[3].to_normalized(),
+                ]
+            })
+            .collect(),
+        vk::Format::R16G16B16A16_UNORM => texels
+            .chunks_exact(2)
+            .map(|c| u16::from_ne_bytes([c[0], c[1]]).to_normalized())
+            .collect(),
+        vk::Format::R16G16B16A16_SFLOAT => texels
+            .chunks_exact(2)
+            .map(|c| f16_to_f32(u16::from_ne_bytes([c[0], c[1]])))
+            .collect(),
+        vk::Format::R32G32B32A32_SFLOAT => texels
+            .chunks_exact(4)
+            .map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+            .collect(),
+        _ => return Err(anyhow!("Cannot convert {:?} texels to RGBA.", format)),
+    };
+
+    Ok(rgba)
+}
+
+/// Converts the texels read back from the depth aspect of a depth image to floats in
+/// the range [0, 1], exactly as the depth test sees them.
+pub fn to_depth_f32(texels: &[u8], format: vk::Format) -> Result<Vec<f32>> {
+    let depth = match format {
+        vk::Format::D32_SFLOAT | vk::Format::D32_SFLOAT_S8_UINT => texels
+            .chunks_exact(4)
+            .map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+            .collect(),
+        // The depth is stored in the lower 24 bits, the upper 8 bits are undefined.
+        vk::Format::D24_UNORM_S8_UINT => texels
+            .chunks_exact(4)
+            .map(|c| (u32::from_ne_bytes([c[0], c[1], c[2], c[3]]) & 0xff_ffff) as f32)
+            .map(|d| d / 0xff_ffff as f32)
+            .collect(),
+        _ => return Err(anyhow!("Cannot convert {:?} texels to depth.", format)),
+    };
+
+    Ok(depth)
+}
+
+/// Reads a PNG file as 8-bit RGBA. Other bit depths and color types are converted,
+/// the values are kept in the encoding of the file (usually sRGB).
+pub fn read_png(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    // Palettes are expanded to RGB(A), 16-bit values reduced to 8 bits.
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+
+    let size = reader
+        .output_buffer_size()
+        .ok_or_else(|| anyhow!("{} is too large.", path.display()))?;
+    let mut buffer = vec![0; size];
+    let info = reader.next_frame(&mut buffer)?;
+    buffer.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|g| [*g, *g, *g, 255]).collect(),
+        png::ColorType::Indexed => {
+            return Err(anyhow!("{} has an unexpanded palette.", path.display()));
+        }
+    };
+
+    Ok((info.width, info.height, rgba))
+}
+
+/// Writes an 8-bit RGBA PNG. The values are expected to be sRGB encoded.
+pub fn write_png(path: &Path, width: u32, height: u32, rgba: &[u8]) -> Result<()> {
+    encode_png(BufWriter::new(File::create(path)?), width, height, rgba)
+}
+
+/// Encodes an 8-bit sRGB RGBA PNG into memory, e.g. to embed it in a report.
+pub fn png_bytes(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    encode_png(&mut bytes, width, height, rgba)?;
+    Ok(bytes)
+}
+
+fn encode_png(writer: impl Write, width: u32, height: u32, rgba: &[u8]) -> Result<()> {
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Writes a 16-bit RGBA PNG. Linear values are tagged with a gamma of 1.0 so viewers
+/// don't mistake them for sRGB encoded ones.
+pub fn write_png16(path: &Path, width: u32, height: u32, rgba: &[u16], linear: bool) -> Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Sixteen);
+    if linear {
+        encoder.set_source_gamma(png::ScaledFloat::new(1.0));
+    } else {
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    }
+
+    // PNG stores 16-bit samples in big-endian byte order.
+    let bytes = rgba
+        .iter()
+        .flat_map(|c| c.to_be_bytes())
+        .collect::<Vec<_>>();
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&bytes)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Writes a 16-bit grayscale PNG of linear values, e.g. depth.
+pub fn write_png16_gray(path: &Path, width: u32, height: u32, values: &[u16]) -> Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Sixteen);
+    encoder.set_source_gamma(png::ScaledFloat::new(1.0));
+
+    let bytes = values
+        .iter()
+        .flat_map(|c| c.to_be_bytes())
+        .collect::<Vec<_>>();
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&bytes)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Writes a color portable float map (PFM) with the RGB channels of the given RGBA values.
+///
+/// The format is a short text header (`PF`, the dimensions and the scale, where a negative
+/// scale means little-endian) followed by the raw 32-bit floats, with the rows stored
+/// from bottom to top.
+pub fn write_pfm(path: &Path, width: u32, height: u32, rgba: &[f32]) -> Result<()> {
+    let rgb = rgba
+        .chunks_exact(4)
+        .flat_map(|p| [p[0], p[1], p[2]])
+        .collect::<Vec<_>>();
+
+    write_pfm_channels(path, width, height, 3, &rgb)
+}
+
+/// Writes a grayscale portable float map (`Pf`), e.g. of depth values.
+pub fn write_pfm_gray(path: &Path, width: u32, height: u32, values: &[f32]) -> Result<()> {
+    write_pfm_channels(path, width, height, 1, values)
+}
+
+fn write_pfm_channels(
+    path: &Path,
+    width: u32,
+    height: u32,
+    channels: usize,
+    values: &[f32],
+) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    let magic = if channels == 1 { "Pf" } else { "PF" };
+    write!(writer, "{magic}\n{width} {height}\n-1.0\n")?;
+
+    let row_length = width as usize * channels;
+    for row in values.chunks_exact(row_length).rev() {
+        for value in row {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Converts an IEEE 754 half-precision float to single precision.
+pub fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half >> 15) as u32) << 31;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+
+    let bits = match (exponent, mantissa) {
+        (0, 0) => sign,
+        // Subnormal half values are normal single precision values.
+        (0, _) => {
+            let shift = mantissa.leading_zeros() - 21;
+            let mantissa = (mantissa << shift) & 0x3ff;
+            sign | ((127 - 15 + 1 - shift) << 23) | (mantissa << 13)
+        }
+        // Infinity and NaN.
+        (0x1f, _) => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+
+    f32::from_bits(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f16_normal_values() {
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x3800), 0.5);
+        assert_eq!(f16_to_f32(0x7bff), 65504.0);
+        assert_eq!(f16_to_f32(0x0400), 2.0f32.powi(-14));
+        assert!((f16_to_f32(0x3555) - 0.333_25).abs() < 1e-5);
+    }
+
+    #[test]
+    fn f16_zeros_keep_their_sign() {
+        assert_eq!(f16_to_f32(0x0000).to_bits(), 0.0f32.to_bits());
+        assert_eq!(f16_to_f32(0x8000).to_bits(), (-0.0f32).to_bits());
+    }
+
+    #[test]
+    fn f16_subnormal_values() {
+        assert_eq!(f16_to_f32(0x0001), 2.0f32.powi(-24));
+        assert_eq!(f16_to_f32(0x8001), -(2.0f32.powi(-24)));
+        assert_eq!(f16_to_f32(0x0200), 2.0f32.powi(-15));
+        assert_eq!(f16_to_f32(0x03ff), 1023.0 * 2.0f32.powi(-24));
+    }
+
+    #[test]
+    fn f16_infinity_and_nan() {
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+}
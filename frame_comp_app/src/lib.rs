@@ -0,0 +1,19 @@
+#![allow(
+    dead_code,
+    unused_variables,
+    clippy::too_many_arguments,
+    clippy::unnecessary_wraps
+)]
+
+pub mod app;
+pub mod capture;
+pub mod clock;
+pub mod comparator;
+pub mod config;
+pub mod image_file;
+pub mod metrics;
+pub mod metrics_log;
+pub mod offline;
+pub mod recording;
+pub mod report;
+pub mod vulkan;
@@ -5,19 +5,19 @@
     clippy::unnecessary_wraps
 )]
 
-mod app;
-mod comparator;
-mod vulkan;
-
 use anyhow::Result;
-use app::App;
+use frame_comp_app::app::App;
+use frame_comp_app::config::{Config, USAGE};
+use frame_comp_app::metrics_log::MetricsFormat;
 use log::{error, info};
 use winit::dpi::LogicalSize;
 use winit::event::{MouseButton, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{Key, NamedKey};
 use winit::window::Window;
 
 struct WindowApp {
+    config: Config,
     window: Option<Window>,
     app: Option<App>,
     minimized: bool,
@@ -26,8 +26,9 @@ struct WindowApp {
 }
 
 impl WindowApp {
-    pub fn new() -> Self {
+    pub fn new(config: Config) -> Self {
         Self {
+            config,
             app: None,
             window: None,
             minimized: false,
@@ -43,15 +44,15 @@ impl winit::application::ApplicationHandler for WindowApp {
             .create_window(
                 Window::default_attributes()
                     .with_inner_size(LogicalSize {
-                        width: 1820,
-                        height: 1090,
+                        width: self.config.width,
+                        height: self.config.height,
                     })
                     .with_title("Vulkan frame comparator app"),
             )
             .unwrap();
         if self.app.is_none() {
             info!("Window resumed, creating application...");
-            match App::create(&window) {
+            match App::create(&window, self.config.clone()) {
                 Ok(app) => self.app = Some(app),
                 Err(e) => {
                     error!("Failed to create application: {:?}", e);
@@ -70,10 +71,8 @@ impl winit::application::ApplicationHandler for WindowApp {
     ) {
         if let (Some(app), Some(window)) = (&mut self.app, &self.window) {
             match event {
-                WindowEvent::RedrawRequested => {
-                    if !self.minimized && !event_loop.exiting() {
-                        app.render(window).unwrap();
-                    }
+                WindowEvent::RedrawRequested if !self.minimized && !event_loop.exiting() => {
+                    app.render(window).unwrap();
                 }
                 WindowEvent::CloseRequested => {
                     app.destroy();
@@ -105,6 +104,50 @@ impl winit::application::ApplicationHandler for WindowApp {
                     app.update(window, self.last_mouse_x, self.mouse_left_pressed);
                     window.request_redraw();
                 }
+                WindowEvent::KeyboardInput { event, .. }
+                    if event.state.is_pressed() && !event.repeat =>
+                {
+                    match event.logical_key.as_ref() {
+                        // Start or stop logging the per-frame metrics.
+                        Key::Character("m") => {
+                            if let Err(e) = app.toggle_metrics_log(MetricsFormat::Csv) {
+                                error!("Failed to toggle the metrics log: {:?}", e);
+                            }
+                        }
+                        Key::Character("M") => {
+                            if let Err(e) = app.toggle_metrics_log(MetricsFormat::JsonLines) {
+                                error!("Failed to toggle the metrics log: {:?}", e);
+                            }
+                        }
+                        // Save the comparator output of the next frame.
+                        Key::Character("p") => app.request_screenshot(),
+                        // Save the comparator inputs of the next frame, each to its own file.
+                        Key::Character("i") => app.request_input_capture(),
+                        // Save the raw depth buffer of the next frame.
+                        Key::Character("d") => app.request_depth_capture(),
+                        // Start or stop recording every frame, or every 10th frame.
+                        Key::Character("r") => {
+                            if let Err(e) = app.toggle_recording(1) {
+                                error!("Failed to toggle the recording: {:?}", e);
+                            }
+                        }
+                        Key::Character("R") => {
+                            if let Err(e) = app.toggle_recording(10) {
+                                error!("Failed to toggle the recording: {:?}", e);
+                            }
+                        }
+                        // Switch between 8-bit PNG, 16-bit PNG and PFM captures.
+                        Key::Character("f") => app.cycle_capture_format(),
+                        // Pause the animation, advance it frame by frame, switch it between
+                        // the wall clock, a fixed step and an explicit time, or rewind it to
+                        // the configured start time.
+                        Key::Named(NamedKey::Space) => app.toggle_pause(),
+                        Key::Character(".") => app.step_frame(),
+                        Key::Character("t") => app.cycle_clock_mode(),
+                        Key::Character("0") => app.set_time(app.data.config.start_time),
+                        _ => (),
+                    }
+                }
                 WindowEvent::DroppedFile(buf) => {
                     println!("{}", buf.display());
                 }
@@ -114,13 +157,37 @@ impl winit::application::ApplicationHandler for WindowApp {
     }
 }
 
+/// Renders a single frame without a window and saves the comparator output, its inputs
+/// and the depth buffer to the working directory.
+fn run_headless(config: Config) -> Result<()> {
+    let mut app = App::create_headless(config)?;
+
+    app.request_screenshot();
+    app.request_input_capture();
+    app.request_depth_capture();
+    let result = app.render_headless();
+
+    app.destroy();
+    result.map(|_| ())
+}
+
 fn main() -> Result<()> {
     pretty_env_logger::init();
 
+    let Some(config) = Config::parse(std::env::args().skip(1))? else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+
+    // Headless mode, e.g. on CI machines without a display.
+    if config.headless {
+        return run_headless(config);
+    }
+
     // Window
     let event_loop = EventLoop::new()?;
     event_loop.set_control_flow(ControlFlow::Poll);
-    let mut window_app = WindowApp::new();
+    let mut window_app = WindowApp::new(config);
     if let Err(e) = event_loop.run_app(&mut window_app) {
         error!("Application event loop returned an error: {:?}", e);
         return Err(e.into());
@@ -0,0 +1,571 @@
+//! CPU reference implementation of the comparison metrics and composed outputs.
+//!
+//! Everything in here works on tightly packed RGBA buffers in host memory and doesn't
+//! need a Vulkan device, so it can be used to check the comparator's output on machines
+//! without a GPU and to compare images offline.
+//!
+//! All metrics are computed on normalized, linear color values in the range [0, 1].
+//! The alpha channel is ignored by the metrics, as the render targets are opaque.
+
+use anyhow::{Result, anyhow};
+
+/// Number of bins of the difference histogram.
+pub const HISTOGRAM_BINS: usize = 256;
+
+/// Default cell size of the checkerboard comparison in pixels.
+pub const CHECKERBOARD_CELL_SIZE: u32 = 32;
+
+// Constants of the SSIM formula for a dynamic range of 1.0: (0.01 * L)^2 and (0.03 * L)^2.
+const SSIM_C1: f64 = 0.01 * 0.01;
+const SSIM_C2: f64 = 0.03 * 0.03;
+
+// The SSIM statistics are computed in an 11x11 Gaussian window with a standard deviation of 1.5.
+const SSIM_WINDOW_RADIUS: usize = 5;
+const SSIM_WINDOW_SIGMA: f64 = 1.5;
+
+/// A single color channel value of an image.
+pub trait Channel: Copy + Default {
+    /// Returns the value normalized to [0, 1].
+    fn to_normalized(self) -> f32;
+    /// Creates a value from a normalized one, clamping it to [0, 1] when needed.
+    fn from_normalized(value: f32) -> Self;
+}
+
+impl Channel for u8 {
+    fn to_normalized(self) -> f32 {
+        self as f32 / 255.0
+    }
+
+    fn from_normalized(value: f32) -> Self {
+        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+    }
+}
+
+impl Channel for u16 {
+    fn to_normalized(self) -> f32 {
+        self as f32 / 65535.0
+    }
+
+    fn from_normalized(value: f32) -> Self {
+        (value.clamp(0.0, 1.0) * 65535.0).round() as u16
+    }
+}
+
+impl Channel for f32 {
+    fn to_normalized(self) -> f32 {
+        self
+    }
+
+    fn from_normalized(value: f32) -> Self {
+        value
+    }
+}
+
+/// A borrowed RGBA image with 4 tightly packed channels per pixel, rows top to bottom.
+#[derive(Clone, Copy, Debug)]
+pub struct RgbaImage<'a, T: Channel> {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: &'a [T],
+    /// Whether the color channels are sRGB encoded. The values are decoded to linear
+    /// before comparing, the same way the hardware does when sampling an sRGB format.
+    pub srgb: bool,
+}
+
+impl<'a, T: Channel> RgbaImage<'a, T> {
+    pub fn new(width: u32, height: u32, pixels: &'a [T], srgb: bool) -> Result<Self> {
+        if pixels.len() != width as usize * height as usize * 4 {
+            return Err(anyhow!(
+                "Expected {} channel values for a {}x{} RGBA image, got {}.",
+                width as usize * height as usize * 4,
+                width,
+                height,
+                pixels.len()
+            ));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+            srgb,
+        })
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// Returns the linear RGB values of the pixel at the given index.
+    fn linear_rgb(&self, index: usize) -> [f32; 3] {
+        let p = &self.pixels[index * 4..index * 4 + 3];
+        let mut rgb = [
+            p[0].to_normalized(),
+            p[1].to_normalized(),
+            p[2].to_normalized(),
+        ];
+        if self.srgb {
+            rgb.iter_mut().for_each(|c| *c = srgb_to_linear(*c));
+        }
+        rgb
+    }
+}
+
+/// The ways two images can be composed into a single output image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompareMode {
+    /// The left part of the output shows the first image, the right part the second one.
+    /// The divider position is in the range [0, 1].
+    Split { divider: f32 },
+    /// The absolute per-channel difference of the two images.
+    Difference,
+    /// Alternating square cells of the two images.
+    Checkerboard { cell_size: u32 },
+}
+
+/// Per-channel (RGB) and overall values of an error metric.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChannelError {
+    pub channels: [f64; 3],
+    pub overall: f64,
+}
+
+/// All the metrics between two images.
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    pub mse: ChannelError,
+    pub psnr: ChannelError,
+    pub ssim: f64,
+    pub max_error: f32,
+    pub changed_pixels: u64,
+    pub histogram: [u32; HISTOGRAM_BINS],
+}
+
+/// Computes all the metrics between two images. Pixels with a channel difference larger
+/// than `tolerance` are counted as changed.
+pub fn compute_metrics<T: Channel>(
+    a: &RgbaImage<T>,
+    b: &RgbaImage<T>,
+    tolerance: f32,
+) -> Result<Metrics> {
+    let mse = mse(a, b)?;
+
+    Ok(Metrics {
+        mse,
+        psnr: psnr(&mse),
+        ssim: ssim(a, b)?,
+        max_error: max_error(a, b)?,
+        changed_pixels: changed_pixel_count(a, b, tolerance)?,
+        histogram: difference_histogram(a, b)?,
+    })
+}
+
+/// Mean squared error of the RGB channels.
+pub fn mse<T: Channel>(a: &RgbaImage<T>, b: &RgbaImage<T>) -> Result<ChannelError> {
+    check_extents(a, b)?;
+
+    let mut sums = [0.0f64; 3];
+    for i in 0..a.pixel_count() {
+        let (pa, pb) = (a.linear_rgb(i), b.linear_rgb(i));
+        for c in 0..3 {
+            let d = (pa[c] - pb[c]) as f64;
+            sums[c] += d * d;
+        }
+    }
+
+    let count = a.pixel_count().max(1) as f64;
+    let channels = sums.map(|s| s / count);
+
+    Ok(ChannelError {
+        channels,
+        overall: channels.iter().sum::<f64>() / 3.0,
+    })
+}
+
+/// Peak signal-to-noise ratio in decibels for a peak value of 1.0.
+/// Identical images have an infinite PSNR.
+pub fn psnr(mse: &ChannelError) -> ChannelError {
+    let psnr = |mse: f64| {
+        if mse == 0.0 {
+            f64::INFINITY
+        } else {
+            -10.0 * mse.log10()
+        }
+    };
+
+    ChannelError {
+        channels: mse.channels.map(psnr),
+        overall: psnr(mse.overall),
+    }
+}
+
+/// The largest absolute difference of any RGB channel.
+pub fn max_error<T: Channel>(a: &RgbaImage<T>, b: &RgbaImage<T>) -> Result<f32> {
+    check_extents(a, b)?;
+
+    Ok((0..a.pixel_count())
+        .map(|i| difference_magnitude(a, b, i))
+        .fold(0.0, f32::max))
+}
+
+/// Number of pixels where any RGB channel differs by more than `tolerance`.
+pub fn changed_pixel_count<T: Channel>(
+    a: &RgbaImage<T>,
+    b: &RgbaImage<T>,
+    tolerance: f32,
+) -> Result<u64> {
+    check_extents(a, b)?;
+
+    Ok((0..a.pixel_count())
+        .filter(|i| difference_magnitude(a, b, *i) > tolerance)
+        .count() as u64)
+}
+
+/// Histogram of the per-pixel difference magnitudes (the largest RGB channel difference).
+/// Bin `i` counts the pixels with a magnitude in [i / 256, (i + 1) / 256).
+pub fn difference_histogram<T: Channel>(
+    a: &RgbaImage<T>,
+    b: &RgbaImage<T>,
+) -> Result<[u32; HISTOGRAM_BINS]> {
+    check_extents(a, b)?;
+
+    let mut histogram = [0u32; HISTOGRAM_BINS];
+    for i in 0..a.pixel_count() {
+        let bin = (difference_magnitude(a, b, i) * HISTOGRAM_BINS as f32) as usize;
+        histogram[bin.min(HISTOGRAM_BINS - 1)] += 1;
+    }
+
+    Ok(histogram)
+}
+
+/// Mean structural similarity of the luminance of the two images.
+pub fn ssim<T: Channel>(a: &RgbaImage<T>, b: &RgbaImage<T>) -> Result<f64> {
+    let map = ssim_map(a, b)?;
+
+    Ok(map.iter().map(|s| *s as f64).sum::<f64>() / map.len().max(1) as f64)
+}
+
+/// Per-pixel structural similarity of the luminance of the two images, row by row.
+/// The local statistics are computed in a Gaussian window, clamped at the image edges.
+pub fn ssim_map<T: Channel>(a: &RgbaImage<T>, b: &RgbaImage<T>) -> Result<Vec<f32>> {
+    check_extents(a, b)?;
+
+    let (width, height) = (a.width as usize, a.height as usize);
+    let luma_a = luminance(a);
+    let luma_b = luminance(b);
+
+    let product = |x: &[f64], y: &[f64]| x.iter().zip(y).map(|(x, y)| x * y).collect::<Vec<_>>();
+
+    let kernel = gaussian_kernel(SSIM_WINDOW_RADIUS, SSIM_WINDOW_SIGMA);
+    let blur = |values: &[f64]| gaussian_blur(values, width, height, &kernel);
+
+    let mu_a = blur(&luma_a);
+    let mu_b = blur(&luma_b);
+    let sq_a = blur(&product(&luma_a, &luma_a));
+    let sq_b = blur(&product(&luma_b, &luma_b));
+    let ab = blur(&product(&luma_a, &luma_b));
+
+    Ok((0..width * height)
+        .map(|i| {
+            let var_a = sq_a[i] - mu_a[i] * mu_a[i];
+            let var_b = sq_b[i] - mu_b[i] * mu_b[i];
+            let covariance = ab[i] - mu_a[i] * mu_b[i];
+
+            let numerator = (2.0 * mu_a[i] * mu_b[i] + SSIM_C1) * (2.0 * covariance + SSIM_C2);
+            let denominator =
+                (mu_a[i] * mu_a[i] + mu_b[i] * mu_b[i] + SSIM_C1) * (var_a + var_b + SSIM_C2);
+
+            (numerator / denominator) as f32
+        })
+        .collect())
+}
+
+/// Composes the two images into one the same way the comparator composes its inputs.
+/// The output has the encoding of the inputs.
+pub fn compose<T: Channel>(
+    a: &RgbaImage<T>,
+    b: &RgbaImage<T>,
+    mode: CompareMode,
+) -> Result<Vec<T>> {
+    check_extents(a, b)?;
+
+    let width = a.width as usize;
+    let mut output = vec![T::default(); a.pixels.len()];
+
+    for (i, pixel) in output.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i % width, i / width);
+        let range = i * 4..i * 4 + 4;
+
+        match mode {
+            CompareMode::Split { divider } => {
+                let left = (x as f32 + 0.5) < divider * a.width as f32;
+                pixel.copy_from_slice(if left {
+                    &a.pixels[range]
+                } else {
+                    &b.pixels[range]
+                });
+            }
+            CompareMode::Checkerboard { cell_size } => {
+                let cell_size = cell_size.max(1) as usize;
+                let even = (x / cell_size + y / cell_size).is_multiple_of(2);
+                pixel.copy_from_slice(if even {
+                    &a.pixels[range]
+                } else {
+                    &b.pixels[range]
+                });
+            }
+            CompareMode::Difference => {
+                let (pa, pb) = (a.linear_rgb(i), b.linear_rgb(i));
+                for c in 0..3 {
+                    let d = (pa[c] - pb[c]).abs();
+                    pixel[c] = T::from_normalized(if a.srgb { linear_to_srgb(d) } else { d });
+                }
+                pixel[3] = T::from_normalized(1.0);
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// Decodes an sRGB encoded value to linear.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear value to sRGB.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Fails if the images don't have the same size.
+pub fn check_extents<T: Channel>(a: &RgbaImage<T>, b: &RgbaImage<T>) -> Result<()> {
+    if a.width != b.width || a.height != b.height {
+        return Err(anyhow!(
+            "Image extents don't match: {}x{} and {}x{}.",
+            a.width,
+            a.height,
+            b.width,
+            b.height
+        ));
+    }
+
+    Ok(())
+}
+
+/// The largest absolute difference of the RGB channels of a pixel.
+fn difference_magnitude<T: Channel>(a: &RgbaImage<T>, b: &RgbaImage<T>, index: usize) -> f32 {
+    let (pa, pb) = (a.linear_rgb(index), b.linear_rgb(index));
+
+    (0..3).map(|c| (pa[c] - pb[c]).abs()).fold(0.0, f32::max)
+}
+
+/// Rec. 709 luminance of every pixel.
+fn luminance<T: Channel>(image: &RgbaImage<T>) -> Vec<f64> {
+    (0..image.pixel_count())
+        .map(|i| {
+            let [r, g, b] = image.linear_rgb(i);
+            0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64
+        })
+        .collect()
+}
+
+fn gaussian_kernel(radius: usize, sigma: f64) -> Vec<f64> {
+    let kernel = (0..=2 * radius)
+        .map(|i| {
+            let x = i as f64 - radius as f64;
+            (-(x * x) / (2.0 * sigma * sigma)).exp()
+        })
+        .collect::<Vec<_>>();
+
+    let sum = kernel.iter().sum::<f64>();
+    kernel.into_iter().map(|k| k / sum).collect()
+}
+
+/// Separable Gaussian blur with clamp-to-edge addressing.
+fn gaussian_blur(values: &[f64], width: usize, height: usize, kernel: &[f64]) -> Vec<f64> {
+    let radius = kernel.len() / 2;
+    let clamp = |v: isize, max: usize| v.clamp(0, max as isize - 1) as usize;
+
+    let mut horizontal = vec![0.0; values.len()];
+    for y in 0..height {
+        for x in 0..width {
+            horizontal[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| {
+                    let sx = clamp(x as isize + k as isize - radius as isize, width);
+                    w * values[y * width + sx]
+                })
+                .sum();
+        }
+    }
+
+    let mut output = vec![0.0; values.len()];
+    for y in 0..height {
+        for x in 0..width {
+            output[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| {
+                    let sy = clamp(y as isize + k as isize - radius as isize, height);
+                    w * horizontal[sy * width + x]
+                })
+                .sum();
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    fn image(width: u32, height: u32, pixels: &[f32]) -> RgbaImage<'_, f32> {
+        RgbaImage::new(width, height, pixels, false).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn identical_images_have_no_error() {
+        let pixels = [
+            0u8, 0, 0, 255, 255, 255, 255, 255, 200, 100, 50, 255, 10, 20, 30, 255,
+        ];
+        let a = RgbaImage::new(2, 2, &pixels, true).unwrap();
+
+        let metrics = compute_metrics(&a, &a, 0.0).unwrap();
+        assert_eq!(metrics.mse.overall, 0.0);
+        assert_eq!(metrics.mse.channels, [0.0; 3]);
+        assert_eq!(metrics.psnr.overall, f64::INFINITY);
+        assert_close(metrics.ssim, 1.0);
+        assert_eq!(metrics.max_error, 0.0);
+        assert_eq!(metrics.changed_pixels, 0);
+        assert_eq!(metrics.histogram[0], 4);
+        assert_eq!(metrics.histogram.iter().sum::<u32>(), 4);
+    }
+
+    #[test]
+    fn single_pixel_difference() {
+        let pixels_a = [BLACK, BLACK].concat();
+        let pixels_b = [BLACK, [0.5, 0.0, 0.0, 1.0]].concat();
+        let (a, b) = (image(2, 1, &pixels_a), image(2, 1, &pixels_b));
+
+        let metrics = compute_metrics(&a, &b, 0.1).unwrap();
+        assert_eq!(metrics.mse.channels, [0.125, 0.0, 0.0]);
+        assert_close(metrics.mse.overall, 0.125 / 3.0);
+        assert_close(metrics.psnr.channels[0], -10.0 * 0.125f64.log10());
+        assert_eq!(metrics.psnr.channels[1], f64::INFINITY);
+        assert_eq!(metrics.max_error, 0.5);
+        assert_eq!(metrics.changed_pixels, 1);
+        assert_eq!(metrics.histogram[0], 1);
+        assert_eq!(metrics.histogram[128], 1);
+        assert!(metrics.ssim < 1.0);
+
+        // A difference within the tolerance doesn't count as a change.
+        assert_eq!(changed_pixel_count(&a, &b, 0.5).unwrap(), 0);
+    }
+
+    #[test]
+    fn srgb_images_are_compared_in_linear_space() {
+        let pixels_a = [0u8, 0, 0, 255];
+        let pixels_b = [128u8, 0, 0, 255];
+        let a = RgbaImage::new(1, 1, &pixels_a, true).unwrap();
+        let b = RgbaImage::new(1, 1, &pixels_b, true).unwrap();
+
+        let expected = srgb_to_linear(128.0 / 255.0);
+        assert_eq!(max_error(&a, &b).unwrap(), expected);
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        for i in 0..=255 {
+            let value = i as f32 / 255.0;
+            let round_trip = linear_to_srgb(srgb_to_linear(value));
+            assert!(
+                (round_trip - value).abs() < 1e-5,
+                "{value} became {round_trip}"
+            );
+        }
+
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_to_linear(0.5) - 0.214_041).abs() < 1e-5);
+    }
+
+    #[test]
+    fn split_divides_at_the_divider() {
+        let pixels_a = [0.0f32; 4 * 4];
+        let pixels_b = [1.0f32; 4 * 4];
+        let (a, b) = (image(4, 1, &pixels_a), image(4, 1, &pixels_b));
+
+        let columns = |divider| {
+            compose(&a, &b, CompareMode::Split { divider })
+                .unwrap()
+                .chunks_exact(4)
+                .map(|p| p[0])
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(columns(0.5), [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(columns(0.25), [0.0, 1.0, 1.0, 1.0]);
+        assert_eq!(columns(0.0), [1.0; 4]);
+        assert_eq!(columns(1.0), [0.0; 4]);
+    }
+
+    #[test]
+    fn checkerboard_alternates_cells() {
+        let pixels_a = [0.0f32; 4 * 4 * 4];
+        let pixels_b = [1.0f32; 4 * 4 * 4];
+        let (a, b) = (image(4, 4, &pixels_a), image(4, 4, &pixels_b));
+
+        let output = compose(&a, &b, CompareMode::Checkerboard { cell_size: 2 }).unwrap();
+        let cells = output.chunks_exact(4).map(|p| p[0]).collect::<Vec<_>>();
+        assert_eq!(
+            cells,
+            [
+                0.0, 0.0, 1.0, 1.0, //
+                0.0, 0.0, 1.0, 1.0, //
+                1.0, 1.0, 0.0, 0.0, //
+                1.0, 1.0, 0.0, 0.0,
+            ]
+        );
+    }
+
+    #[test]
+    fn difference_is_absolute_and_opaque() {
+        let pixels_a = [0.25f32, 0.75, 0.5, 0.0];
+        let pixels_b = [0.75f32, 0.25, 0.5, 0.0];
+        let (a, b) = (image(1, 1, &pixels_a), image(1, 1, &pixels_b));
+
+        let output = compose(&a, &b, CompareMode::Difference).unwrap();
+        assert_eq!(output, [0.5, 0.5, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn mismatched_images_are_rejected() {
+        let pixels = [0.0f32; 4 * 2];
+        let (a, b) = (image(2, 1, &pixels), image(1, 2, &pixels));
+
+        assert!(check_extents(&a, &b).is_err());
+        assert!(compute_metrics(&a, &b, 0.0).is_err());
+        assert!(compose(&a, &b, CompareMode::Difference).is_err());
+        assert!(RgbaImage::new(2, 2, &pixels, false).is_err());
+    }
+}
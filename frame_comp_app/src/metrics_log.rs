@@ -0,0 +1,261 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use log::info;
+
+/// The file formats the per-frame metrics can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricsFormat {
+    /// Comma separated values with a header line.
+    Csv,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+impl MetricsFormat {
+    /// Picks the format by the file extension, `.jsonl` and `.json` select JSON Lines,
+    /// everything else CSV.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("jsonl") | Some("json") => Self::JsonLines,
+            _ => Self::Csv,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::JsonLines => "jsonl",
+        }
+    }
+}
+
+/// The metrics of a single presented frame.
+#[derive(Clone, Debug)]
+pub struct MetricsRecord {
+    pub frame: u64,
+    /// Seconds since the app has started.
+    pub elapsed: f64,
+    pub divider_position: f32,
+    /// Named metric values. A metric that wasn't computed for this frame is `None`.
+    /// Every record of a log must contain the same metrics in the same order.
+    pub metrics: Vec<(&'static str, Option<f64>)>,
+}
+
+/// Appends one record per frame to a CSV or JSON Lines file, so the metrics can be
+/// plotted over time (e.g. across a camera animation).
+pub struct MetricsLog {
+    path: PathBuf,
+    format: MetricsFormat,
+    writer: BufWriter<File>,
+    header_written: bool,
+}
+
+impl MetricsLog {
+    pub fn create(path: impl Into<PathBuf>, format: MetricsFormat) -> Result<Self> {
+        let path = path.into();
+        let writer = BufWriter::new(File::create(&path)?);
+        info!("Writing frame metrics to {}.", path.display());
+
+        Ok(Self {
+            path,
+            format,
+            writer,
+            header_written: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&mut self, record: &MetricsRecord) -> Result<()> {
+        match self.format {
+            MetricsFormat::Csv => self.append_csv(record),
+            MetricsFormat::JsonLines => self.append_json(record),
+        }
+    }
+
+    /// Flushes the buffered records to the file.
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    fn append_csv(&mut self, record: &MetricsRecord) -> Result<()> {
+        // The columns are given by the metrics of the first record.
+        if !self.header_written {
+            write!(self.writer, "frame,elapsed,divider_position")?;
+            for (name, _) in &record.metrics {
+                write!(self.writer, ",{name}")?;
+            }
+            writeln!(self.writer)?;
+            self.header_written = true;
+        }
+
+        write!(
+            self.writer,
+            "{},{},{}",
+            record.frame, record.elapsed, record.divider_position
+        )?;
+        for (_, value) in &record.metrics {
+            match value {
+                Some(value) => write!(self.writer, ",{value}")?,
+                None => write!(self.writer, ",")?,
+            }
+        }
+        writeln!(self.writer)?;
+
+        Ok(())
+    }
+
+    fn append_json(&mut self, record: &MetricsRecord) -> Result<()> {
+        write!(
+            self.writer,
+            "{{\"frame\":{},\"elapsed\":{},\"divider_position\":{}",
+            record.frame,
+            json_number(record.elapsed),
+            json_number(record.divider_position as f64)
+        )?;
+        for (name, value) in &record.metrics {
+            let value = value.map_or_else(|| "null".to_string(), json_number);
+            write!(self.writer, ",\"{name}\":{value}")?;
+        }
+        writeln!(self.writer, "}}")?;
+
+        Ok(())
+    }
+}
+
+impl Drop for MetricsLog {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+/// Quotes and escapes a string for JSON.
+pub fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// JSON has no representation for infinity and NaN (e.g. the PSNR of identical images).
+pub fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(frame: u64, metrics: Vec<(&'static str, Option<f64>)>) -> MetricsRecord {
+        MetricsRecord {
+            frame,
+            elapsed: 1.25,
+            divider_position: 0.5,
+            metrics,
+        }
+    }
+
+    /// Writes the records to a temporary file and returns its contents.
+    fn write(name: &str, format: MetricsFormat, records: &[MetricsRecord]) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "metrics_log_{}_{name}.{}",
+            std::process::id(),
+            format.extension()
+        ));
+
+        let mut log = MetricsLog::create(&path, format).unwrap();
+        for record in records {
+            log.append(record).unwrap();
+        }
+        log.flush().unwrap();
+        drop(log);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        contents
+    }
+
+    #[test]
+    fn format_from_path() {
+        let format = |path: &str| MetricsFormat::from_path(Path::new(path));
+        assert_eq!(format("metrics.jsonl"), MetricsFormat::JsonLines);
+        assert_eq!(format("metrics.json"), MetricsFormat::JsonLines);
+        assert_eq!(format("metrics.csv"), MetricsFormat::Csv);
+        assert_eq!(format("metrics"), MetricsFormat::Csv);
+    }
+
+    #[test]
+    fn csv_has_a_header_and_empty_cells() {
+        let contents = write(
+            "csv",
+            MetricsFormat::Csv,
+            &[
+                record(0, vec![("psnr", Some(30.5)), ("ssim", None)]),
+                record(1, vec![("psnr", None), ("ssim", Some(0.75))]),
+            ],
+        );
+
+        assert_eq!(
+            contents,
+            "frame,elapsed,divider_position,psnr,ssim\n0,1.25,0.5,30.5,\n1,1.25,0.5,,0.75\n"
+        );
+    }
+
+    #[test]
+    fn json_lines_have_one_object_per_frame() {
+        let contents = write(
+            "jsonl",
+            MetricsFormat::JsonLines,
+            &[
+                record(0, vec![("psnr", Some(f64::INFINITY)), ("ssim", None)]),
+                record(1, vec![("psnr", Some(30.5)), ("ssim", Some(0.75))]),
+            ],
+        );
+
+        assert_eq!(
+            contents,
+            concat!(
+                "{\"frame\":0,\"elapsed\":1.25,\"divider_position\":0.5,",
+                "\"psnr\":null,\"ssim\":null}\n",
+                "{\"frame\":1,\"elapsed\":1.25,\"divider_position\":0.5,",
+                "\"psnr\":30.5,\"ssim\":0.75}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn json_string_escapes() {
+        assert_eq!(json_string(""), "\"\"");
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("a \"b\""), "\"a \\\"b\\\"\"");
+        assert_eq!(json_string("C:\\frames"), "\"C:\\\\frames\"");
+        assert_eq!(json_string("a\nb\t\u{1}"), "\"a\\u000ab\\u0009\\u0001\"");
+        assert_eq!(json_string("ünïcode"), "\"ünïcode\"");
+    }
+
+    #[test]
+    fn json_number_of_non_finite_values() {
+        assert_eq!(json_number(0.5), "0.5");
+        assert_eq!(json_number(-3.0), "-3");
+        assert_eq!(json_number(f64::INFINITY), "null");
+        assert_eq!(json_number(f64::NEG_INFINITY), "null");
+        assert_eq!(json_number(f64::NAN), "null");
+    }
+}
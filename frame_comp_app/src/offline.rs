@@ -0,0 +1,449 @@
+//! Comparing image files without the interactive app, e.g. frames exported by a renderer.
+//!
+//! The images are uploaded as textures and composed by the same `RenderTargetComparator`
+//! the app uses, on a headless device. Without a Vulkan device the CPU reference
+//! implementation in `metrics` composes them instead.
+
+use std::path::Path;
+use std::ptr::copy_nonoverlapping as memcpy;
+use std::rc::Rc;
+
+use anyhow::{Result, anyhow};
+use rtcmp::{CompareInfo, RenderTargetComparator, RenderTargetComparatorCreateInfo};
+use vulkanalia::loader::{LIBRARY, LibloadingLoader};
+use vulkanalia::prelude::v1_3::*;
+use vulkanalia::vk::ExtDebugUtilsExtensionInstanceCommands;
+
+use crate::app::AppData;
+use crate::image_file::{read_png, to_rgba8, write_png};
+use crate::metrics::{CompareMode, Metrics, RgbaImage, check_extents, compose};
+use crate::metrics_log::{json_number, json_string};
+use crate::vulkan::buffers::buffer::create_buffer;
+use crate::vulkan::commands::{
+    begin_single_time_commands, create_command_pool, end_single_time_commands,
+};
+use crate::vulkan::device::create_logical_device;
+use crate::vulkan::image::{
+    copy_buffer_to_image, create_image, create_image_view, transition_image_layout,
+};
+use crate::vulkan::instance::create_instance;
+use crate::vulkan::offscreen::OFFSCREEN_FORMAT;
+use crate::vulkan::physical_device::pick_physical_device;
+use crate::vulkan::readback::ReadbackBuffer;
+
+/// An 8-bit sRGB RGBA image in host memory, rows top to bottom.
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn read(path: &Path) -> Result<Self> {
+        let (width, height, pixels) = read_png(path)?;
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        write_png(path, self.width, self.height, &self.pixels)
+    }
+
+    pub fn as_rgba(&self) -> Result<RgbaImage<'_, u8>> {
+        RgbaImage::new(self.width, self.height, &self.pixels, true)
+    }
+}
+
+/// A headless Vulkan device that composes pairs of images with the frame comparator.
+pub struct GpuComparator {
+    entry: Entry,
+    instance: Instance,
+    device: Rc<Device>,
+    data: AppData,
+}
+
+impl GpuComparator {
+    /// Creates a device without a window or surface. Fails if there is no suitable
+    /// Vulkan device, in which case the images can be composed on the CPU.
+    pub fn create() -> Result<Self> {
+        let loader = unsafe { LibloadingLoader::new(LIBRARY) }?;
+        let entry = unsafe { Entry::new(loader).map_err(|b| anyhow!("{}", b)) }?;
+        let mut data = AppData::default();
+
+        let instance = create_instance(None, &entry, &mut data)?;
+        // Failing to find a device is expected on machines without a GPU, where the
+        // caller falls back to the CPU, so the instance mustn't leak.
+        let device = match create_device(&entry, &instance, &mut data) {
+            Ok(device) => Rc::new(device),
+            Err(e) => {
+                destroy_instance(&instance, &data);
+                return Err(e);
+            }
+        };
+
+        Ok(Self {
+            entry,
+            instance,
+            device,
+            data,
+        })
+    }
+
+    /// The name of the device the images are composed on.
+    pub fn device_name(&self) -> String {
+        self.data.physical_device_properties.device_name.to_string()
+    }
+
+    /// Composes the two images side by side, split at the divider position in [0, 1],
+    /// exactly as the app shows its render targets.
+    pub fn compose(&mut self, a: &Image, b: &Image, divider_position: f32) -> Result<Image> {
+        check_extents(&a.as_rgba()?, &b.as_rgba()?)?;
+
+        // Everything created for the composition is collected here and destroyed
+        // afterwards, also when one of the steps fails halfway.
+        let device = Rc::clone(&self.device);
+        let mut resources = ComposeResources::default();
+        let result = self.compose_with(&device, &mut resources, a, b, divider_position);
+        resources.destroy(&device);
+
+        result
+    }
+
+    fn compose_with(
+        &mut self,
+        device: &Device,
+        resources: &mut ComposeResources,
+        a: &Image,
+        b: &Image,
+        divider_position: f32,
+    ) -> Result<Image> {
+        let extent = vk::Extent2D {
+            width: a.width,
+            height: a.height,
+        };
+
+        let a_view = self.upload(device, resources, a)?;
+        let b_view = self.upload(device, resources, b)?;
+
+        let (output, output_memory) = create_image(
+            &self.instance,
+            device,
+            &mut self.data,
+            extent.width,
+            extent.height,
+            1, // mip levels
+            vk::SampleCountFlags::_1,
+            OFFSCREEN_FORMAT,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+        resources.images.push((output, output_memory));
+        let output_view = create_image_view(
+            device,
+            output,
+            OFFSCREEN_FORMAT,
+            vk::ImageAspectFlags::COLOR,
+            1,
+        )?;
+        resources.image_views.push(output_view);
+
+        // The comparator only needs the samplers of its inputs.
+        let sampler_size = vk::DescriptorPoolSize::builder()
+            .type_(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(RenderTargetComparator::image_sampler_count());
+        let pool_sizes = &[sampler_size];
+        let info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(pool_sizes)
+            .max_sets(RenderTargetComparator::image_sampler_count());
+        resources.descriptor_pool = unsafe { device.create_descriptor_pool(&info, None) }?;
+
+        let viewport = vk::Viewport::builder()
+            .width(extent.width as f32)
+            .height(extent.height as f32)
+            .max_depth(1.0)
+            .build();
+
+        let info = RenderTargetComparatorCreateInfo::builder()
+            .device(Rc::clone(&self.device))
+            .descriptor_pool(resources.descriptor_pool)
+            .format(OFFSCREEN_FORMAT)
+            .extent(extent)
+            .in_image_views([a_view, b_view])
+            .viewport(viewport)
+            .out_image_view(output_view)
+            // There is no swapchain, the output is only copied back to the host.
+            .final_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .build()?;
+        let comparator = resources
+            .comparator
+            .insert(RenderTargetComparator::new(&info)?);
+
+        let readback = resources.readback.insert(ReadbackBuffer::create(
+            &self.instance,
+            device,
+            &mut self.data,
+            extent,
+            OFFSCREEN_FORMAT,
+        )?);
+
+        let command_buffer = begin_single_time_commands(device, &self.data)?;
+        let compare_info = CompareInfo::builder()
+            .command_buffer(command_buffer)
+            .divider_position(divider_position)
+            .build()?;
+        // The descriptor pool above is sized for the single comparison recorded here.
+        unsafe { comparator.compare(&compare_info) }?;
+
+        readback.record_copy(
+            device,
+            command_buffer,
+            output,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::ImageAspectFlags::COLOR,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::AccessFlags::MEMORY_WRITE,
+        );
+        end_single_time_commands(device, &self.data, command_buffer)?;
+
+        let texels = readback.read(device)?;
+
+        Ok(Image {
+            width: extent.width,
+            height: extent.height,
+            pixels: to_rgba8(&texels, OFFSCREEN_FORMAT)?,
+        })
+    }
+
+    /// Uploads an image to a sampled texture, ready for the comparator, and returns its view.
+    /// The texture is added to the resources as soon as it exists.
+    fn upload(
+        &mut self,
+        device: &Device,
+        resources: &mut ComposeResources,
+        image: &Image,
+    ) -> Result<vk::ImageView> {
+        let format = vk::Format::R8G8B8A8_SRGB;
+        let size = image.pixels.len() as u64;
+
+        let (staging_buffer, staging_buffer_memory) = create_buffer(
+            &self.instance,
+            device,
+            &mut self.data,
+            size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        let result = self.upload_from(
+            device,
+            resources,
+            image,
+            format,
+            staging_buffer,
+            staging_buffer_memory,
+        );
+
+        unsafe {
+            device.destroy_buffer(staging_buffer, None);
+            device.free_memory(staging_buffer_memory, None);
+        }
+
+        let texture = result?;
+        let view = create_image_view(device, texture, format, vk::ImageAspectFlags::COLOR, 1)?;
+        resources.image_views.push(view);
+
+        Ok(view)
+    }
+
+    fn upload_from(
+        &mut self,
+        device: &Device,
+        resources: &mut ComposeResources,
+        image: &Image,
+        format: vk::Format,
+        staging_buffer: vk::Buffer,
+        staging_buffer_memory: vk::DeviceMemory,
+    ) -> Result<vk::Image> {
+        let size = image.pixels.len() as u64;
+
+        unsafe {
+            let memory =
+                device.map_memory(staging_buffer_memory, 0, size, vk::MemoryMapFlags::empty())?;
+            memcpy(image.pixels.as_ptr(), memory.cast(), image.pixels.len());
+            device.unmap_memory(staging_buffer_memory);
+        }
+
+        let (texture, texture_memory) = create_image(
+            &self.instance,
+            device,
+            &mut self.data,
+            image.width,
+            image.height,
+            1, // mip levels
+            vk::SampleCountFlags::_1,
+            format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+        resources.images.push((texture, texture_memory));
+
+        transition_image_layout(
+            device,
+            &self.data,
+            texture,
+            format,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            1,
+        )?;
+        copy_buffer_to_image(
+            device,
+            &self.data,
+            staging_buffer,
+            texture,
+            image.width,
+            image.height,
+        )?;
+        transition_image_layout(
+            device,
+            &self.data,
+            texture,
+            format,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            1,
+        )?;
+
+        Ok(texture)
+    }
+
+    pub fn destroy(&mut self) {
+        unsafe {
+            self.device.device_wait_idle().unwrap();
+            self.device
+                .destroy_command_pool(self.data.command_pool, None);
+            self.device.destroy_device(None);
+        }
+        destroy_instance(&self.instance, &self.data);
+    }
+}
+
+/// Picks a physical device and creates the logical device and its command pool.
+fn create_device(entry: &Entry, instance: &Instance, data: &mut AppData) -> Result<Device> {
+    pick_physical_device(instance, data)?;
+    let device = create_logical_device(entry, instance, data)?;
+    if let Err(e) = create_command_pool(instance, &device, data) {
+        unsafe { device.destroy_device(None) };
+        return Err(e);
+    }
+
+    Ok(device)
+}
+
+/// Destroys the debug messenger, if validation is enabled, and the instance.
+fn destroy_instance(instance: &Instance, data: &AppData) {
+    unsafe {
+        if data.config.validation {
+            instance.destroy_debug_utils_messenger_ext(data.messenger, None);
+        }
+        instance.destroy_instance(None);
+    }
+}
+
+/// The Vulkan objects created for a single composition.
+#[derive(Default)]
+struct ComposeResources {
+    comparator: Option<RenderTargetComparator>,
+    readback: Option<ReadbackBuffer>,
+    descriptor_pool: vk::DescriptorPool,
+    image_views: Vec<vk::ImageView>,
+    images: Vec<(vk::Image, vk::DeviceMemory)>,
+}
+
+impl ComposeResources {
+    /// Destroys whatever has been created so far, in reverse order of creation.
+    fn destroy(&mut self, device: &Device) {
+        // A failed step may leave commands running, e.g. when a submission failed to wait.
+        unsafe { device.device_wait_idle() }.ok();
+
+        // The comparator frees its descriptor sets and pipelines when it is dropped, which
+        // has to happen before its descriptor pool is destroyed.
+        self.comparator.take();
+
+        unsafe {
+            if let Some(readback) = self.readback.take() {
+                readback.destroy(device);
+            }
+            if !self.descriptor_pool.is_null() {
+                device.destroy_descriptor_pool(self.descriptor_pool, None);
+            }
+            for view in self.image_views.drain(..) {
+                device.destroy_image_view(view, None);
+            }
+            for (image, memory) in self.images.drain(..) {
+                device.destroy_image(image, None);
+                device.free_memory(memory, None);
+            }
+        }
+    }
+}
+
+/// Composes the two images split at the divider position on the CPU, the fallback for
+/// machines without a Vulkan device.
+pub fn compose_on_cpu(a: &Image, b: &Image, divider_position: f32) -> Result<Image> {
+    let pixels = compose(
+        &a.as_rgba()?,
+        &b.as_rgba()?,
+        CompareMode::Split {
+            divider: divider_position,
+        },
+    )?;
+
+    Ok(Image {
+        width: a.width,
+        height: a.height,
+        pixels,
+    })
+}
+
+/// The metrics as a JSON object, together with the name of the device that composed
+/// the images (or `"cpu"`).
+pub fn metrics_json(metrics: &Metrics, composed_on: &str) -> String {
+    let channels = |values: [f64; 3]| {
+        values
+            .iter()
+            .map(|v| json_number(*v))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let histogram = metrics
+        .histogram
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!(
+        concat!(
+            "{{\"composed_on\":{},",
+            "\"mse\":{},\"mse_rgb\":[{}],\"psnr\":{},\"psnr_rgb\":[{}],",
+            "\"ssim\":{},\"max_error\":{},\"changed_pixels\":{},\"histogram\":[{}]}}\n",
+        ),
+        json_string(composed_on),
+        json_number(metrics.mse.overall),
+        channels(metrics.mse.channels),
+        json_number(metrics.psnr.overall),
+        channels(metrics.psnr.channels),
+        json_number(metrics.ssim),
+        json_number(metrics.max_error as f64),
+        metrics.changed_pixels,
+        histogram,
+    )
+}
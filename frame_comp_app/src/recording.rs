@@ -0,0 +1,206 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Sender, channel};
+use std::thread::JoinHandle;
+
+use anyhow::{Result, anyhow};
+use log::{error, info};
+use vulkanalia::prelude::v1_3::*;
+
+use crate::app::{AppData, MAX_FRAMES_IN_FLIGHT};
+use crate::comparator::output_layout;
+use crate::image_file::{to_rgba8, write_png};
+use crate::vulkan::commands::begin_single_time_commands;
+use crate::vulkan::readback::ReadbackBuffer;
+
+/// Saves the presented frames to a numbered PNG sequence (`frame_000000.png`, ...),
+/// e.g. to turn them into a video with `ffmpeg -i frame_%06d.png`.
+///
+/// Unlike screenshots, recording must not stall the render loop. Every frame in flight
+/// has its own staging buffer that the swapchain image is copied into together with the
+/// frame. The buffer is read only once the frame's fence has been waited for anyway,
+/// i.e. as many frames later as there are frames in flight, and the PNGs are encoded on a
+/// separate thread.
+pub struct Recorder {
+    directory: PathBuf,
+    /// Only every `interval`-th frame is recorded.
+    interval: u64,
+    /// The number of the next file in the sequence.
+    next_index: u64,
+    /// Sized for the most frames in flight, the configured ones use the first slots.
+    slots: [RecordingSlot; MAX_FRAMES_IN_FLIGHT],
+    sender: Option<Sender<RecordedFrame>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+/// The staging buffer of one frame in flight and the copy that is pending in it.
+#[derive(Clone, Copy, Debug, Default)]
+struct RecordingSlot {
+    readback: ReadbackBuffer,
+    command_buffer: vk::CommandBuffer,
+    /// The file number of the copied frame, if a copy was submitted.
+    pending: Option<u64>,
+}
+
+/// A frame read back from the GPU that waits to be written to disk.
+struct RecordedFrame {
+    path: PathBuf,
+    extent: vk::Extent2D,
+    format: vk::Format,
+    texels: Vec<u8>,
+}
+
+impl Recorder {
+    /// Creates the directory of the sequence and starts the thread that writes the files.
+    pub fn start(directory: impl Into<PathBuf>, interval: u64) -> Result<Self> {
+        if interval == 0 {
+            return Err(anyhow!("The recording interval must be at least 1."));
+        }
+
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+
+        let (sender, receiver) = channel::<RecordedFrame>();
+        let writer = std::thread::spawn(move || {
+            for frame in receiver {
+                if let Err(e) = write_frame(&frame) {
+                    error!("Failed to write {}: {:?}", frame.path.display(), e);
+                }
+            }
+        });
+
+        info!("Recording frames to {}.", directory.display());
+
+        Ok(Self {
+            directory,
+            interval,
+            next_index: 0,
+            slots: Default::default(),
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Hands the copy that was submitted with the last use of this frame in flight over
+    /// to the writer thread. The frame's fence must have been waited for.
+    pub fn collect(&mut self, device: &Device, command_pool: vk::CommandPool, frame: usize) {
+        let slot = &mut self.slots[frame];
+        let Some(index) = slot.pending.take() else {
+            return;
+        };
+
+        unsafe { device.free_command_buffers(command_pool, &[slot.command_buffer]) };
+        slot.command_buffer = vk::CommandBuffer::null();
+
+        let texels = match slot.readback.read(device) {
+            Ok(texels) => texels,
+            Err(e) => {
+                error!("Failed to read back recorded frame {}: {:?}", index, e);
+                return;
+            }
+        };
+
+        let recorded = RecordedFrame {
+            path: self.directory.join(format!("frame_{index:06}.png")),
+            extent: slot.readback.extent,
+            format: slot.readback.format,
+            texels,
+        };
+
+        if let Some(sender) = &self.sender {
+            // The writer thread only stops once the sender is dropped.
+            let _ = sender.send(recorded);
+        }
+    }
+
+    /// Records the copy of the swapchain image into the staging buffer of this frame in
+    /// flight, if the frame is due. The returned command buffer has to be submitted right
+    /// after the frame's command buffer, before the image is presented.
+    pub fn record(
+        &mut self,
+        instance: &Instance,
+        device: &Device,
+        data: &mut AppData,
+        frame: usize,
+        image_index: usize,
+        frame_count: u64,
+    ) -> Result<Option<vk::CommandBuffer>> {
+        if !frame_count.is_multiple_of(self.interval) {
+            return Ok(None);
+        }
+
+        if !data
+            .swapchain_usage
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+        {
+            return Err(anyhow!("The swapchain images can't be copied from."));
+        }
+
+        // The staging buffers are created lazily and follow the swapchain when resized.
+        let (extent, format) = (data.swapchain_extent, data.swapchain_format);
+        let slot = &mut self.slots[frame];
+        if slot.readback.extent != extent || slot.readback.format != format {
+            slot.readback.destroy(device);
+            slot.readback = ReadbackBuffer::create(instance, device, data, extent, format)?;
+        }
+
+        let command_buffer = begin_single_time_commands(device, data)?;
+
+        slot.readback.record_copy(
+            device,
+            command_buffer,
+            data.swapchain_images[image_index],
+            output_layout(data),
+            vk::ImageAspectFlags::COLOR,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::AccessFlags::MEMORY_WRITE,
+        );
+
+        if let Err(e) = unsafe { device.end_command_buffer(command_buffer) } {
+            unsafe { device.free_command_buffers(data.command_pool, &[command_buffer]) };
+            return Err(e.into());
+        }
+
+        slot.command_buffer = command_buffer;
+        slot.pending = Some(self.next_index);
+        self.next_index += 1;
+
+        Ok(Some(command_buffer))
+    }
+
+    /// Waits for all the pending copies, writes the remaining files and frees the
+    /// staging buffers.
+    pub fn stop(mut self, device: &Device, command_pool: vk::CommandPool) -> Result<()> {
+        unsafe { device.device_wait_idle() }?;
+
+        for frame in 0..MAX_FRAMES_IN_FLIGHT {
+            self.collect(device, command_pool, frame);
+        }
+        self.slots.iter().for_each(|s| s.readback.destroy(device));
+
+        // Dropping the sender ends the writer thread once it has written every frame.
+        self.sender = None;
+        if let Some(writer) = self.writer.take() {
+            writer
+                .join()
+                .map_err(|_| anyhow!("The recording writer thread panicked."))?;
+        }
+
+        info!(
+            "Recorded {} frames to {}.",
+            self.next_index,
+            self.directory.display()
+        );
+
+        Ok(())
+    }
+}
+
+fn write_frame(frame: &RecordedFrame) -> Result<()> {
+    let rgba = to_rgba8(&frame.texels, frame.format)?;
+    write_png(&frame.path, frame.extent.width, frame.extent.height, &rgba)
+}
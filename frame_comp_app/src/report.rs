@@ -0,0 +1,426 @@
+//! Batch comparisons of two directories of images, e.g. the frames exported before and
+//! after a renderer change, summarized in a self-contained HTML report.
+//!
+//! The report embeds thumbnails of both images and their difference as data URIs, so it
+//! can be archived or shared as a single file.
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use log::info;
+
+use crate::image_file::png_bytes;
+use crate::metrics::{
+    CompareMode, Metrics, check_extents, compose, compute_metrics, linear_to_srgb, srgb_to_linear,
+};
+use crate::offline::Image;
+
+/// The width the thumbnails in the report are reduced to.
+pub const THUMBNAIL_WIDTH: u32 = 256;
+
+/// The outcome of comparing one pair of images with the same name.
+pub enum ReportEntry {
+    Compared {
+        name: String,
+        /// Boxed, as the histogram makes the metrics much larger than a failure.
+        metrics: Box<Metrics>,
+        /// Thumbnails of A, B and their difference.
+        thumbnails: [Image; 3],
+    },
+    /// The pair couldn't be compared, e.g. because one of the images is missing or they
+    /// have different sizes.
+    Failed { name: String, reason: String },
+}
+
+impl ReportEntry {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Compared { name, .. } | Self::Failed { name, .. } => name,
+        }
+    }
+
+    /// Orders the entries from the worst to the best: failures first, then by SSIM and
+    /// PSNR, both ascending.
+    fn worst_first(&self, other: &Self) -> std::cmp::Ordering {
+        let key = |entry: &Self| match entry {
+            Self::Compared { metrics, .. } => (1, metrics.ssim, metrics.psnr.overall),
+            Self::Failed { .. } => (0, 0.0, 0.0),
+        };
+        let (a, b) = (key(self), key(other));
+        a.0.cmp(&b.0)
+            .then(a.1.total_cmp(&b.1))
+            .then(a.2.total_cmp(&b.2))
+            .then_with(|| self.name().cmp(other.name()))
+    }
+}
+
+/// Compares every PNG in directory `a` with the one of the same name in directory `b`.
+/// Images that only exist in one of the directories are reported as failed. The entries
+/// are sorted from the worst to the best.
+pub fn compare_directories(a: &Path, b: &Path, tolerance: f32) -> Result<Vec<ReportEntry>> {
+    let (names_a, names_b) = (png_names(a)?, png_names(b)?);
+    if names_a.is_empty() && names_b.is_empty() {
+        return Err(anyhow!(
+            "Neither {} nor {} contains any PNG files.",
+            a.display(),
+            b.display()
+        ));
+    }
+
+    let mut entries = vec![];
+    for name in names_a.union(&names_b) {
+        let entry = if !names_b.contains(name) {
+            ReportEntry::Failed {
+                name: name.clone(),
+                reason: format!("missing in {}", b.display()),
+            }
+        } else if !names_a.contains(name) {
+            ReportEntry::Failed {
+                name: name.clone(),
+                reason: format!("missing in {}", a.display()),
+            }
+        } else {
+            compare_pair(name, &a.join(name), &b.join(name), tolerance).unwrap_or_else(|e| {
+                ReportEntry::Failed {
+                    name: name.clone(),
+                    reason: e.to_string(),
+                }
+            })
+        };
+
+        info!("Compared {}.", entry.name());
+        entries.push(entry);
+    }
+
+    entries.sort_by(ReportEntry::worst_first);
+    Ok(entries)
+}
+
+fn png_names(directory: &Path) -> Result<BTreeSet<String>> {
+    let entries = std::fs::read_dir(directory).map_err(|e| {
+        anyhow!(
+            "Failed to read the directory {}: {}",
+            directory.display(),
+            e
+        )
+    })?;
+
+    let mut names = BTreeSet::new();
+    for entry in entries {
+        let path = entry?.path();
+        let is_png = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("png"));
+        if path.is_file()
+            && is_png
+            && let Some(name) = path.file_name().and_then(|n| n.to_str())
+        {
+            names.insert(name.to_string());
+        }
+    }
+
+    Ok(names)
+}
+
+fn compare_pair(name: &str, a: &Path, b: &Path, tolerance: f32) -> Result<ReportEntry> {
+    let (a, b) = (Image::read(a)?, Image::read(b)?);
+    let (rgba_a, rgba_b) = (a.as_rgba()?, b.as_rgba()?);
+    check_extents(&rgba_a, &rgba_b)?;
+
+    let metrics = compute_metrics(&rgba_a, &rgba_b, tolerance)?;
+    let difference = Image {
+        width: a.width,
+        height: a.height,
+        pixels: compose(&rgba_a, &rgba_b, CompareMode::Difference)?,
+    };
+
+    Ok(ReportEntry::Compared {
+        name: name.to_string(),
+        metrics: Box::new(metrics),
+        thumbnails: [&a, &b, &difference].map(|i| thumbnail(i, THUMBNAIL_WIDTH)),
+    })
+}
+
+/// Reduces the image by an integer factor so it is at most `max_width` wide. The pixels
+/// of each block are averaged in linear space.
+pub fn thumbnail(image: &Image, max_width: u32) -> Image {
+    let factor = image.width.div_ceil(max_width.max(1)).max(1);
+    let (width, height) = (image.width.div_ceil(factor), image.height.div_ceil(factor));
+
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0f32; 4];
+            let mut count = 0.0;
+            for sy in y * factor..((y + 1) * factor).min(image.height) {
+                for sx in x * factor..((x + 1) * factor).min(image.width) {
+                    let i = ((sy * image.width + sx) * 4) as usize;
+                    for (c, value) in image.pixels[i..i + 4].iter().enumerate() {
+                        let value = *value as f32 / 255.0;
+                        sum[c] += if c < 3 { srgb_to_linear(value) } else { value };
+                    }
+                    count += 1.0;
+                }
+            }
+
+            for (c, value) in sum.iter().enumerate() {
+                let value = value / count;
+                let value = if c < 3 { linear_to_srgb(value) } else { value };
+                pixels.push((value * 255.0).round().clamp(0.0, 255.0) as u8);
+            }
+        }
+    }
+
+    Image {
+        width,
+        height,
+        pixels,
+    }
+}
+
+/// Renders the entries as an HTML page with a sortable table. The rows keep the order of
+/// the entries until a column header is clicked.
+pub fn html_report(a: &Path, b: &Path, entries: &[ReportEntry]) -> Result<String> {
+    let failed = entries
+        .iter()
+        .filter(|e| matches!(e, ReportEntry::Failed { .. }))
+        .count();
+    let changed = entries
+        .iter()
+        .filter(
+            |e| matches!(e, ReportEntry::Compared { metrics, .. } if metrics.changed_pixels > 0),
+        )
+        .count();
+
+    let mut rows = String::new();
+    for entry in entries {
+        match entry {
+            ReportEntry::Compared {
+                name,
+                metrics,
+                thumbnails,
+            } => {
+                let mut images = String::new();
+                for thumbnail in thumbnails {
+                    let png = png_bytes(thumbnail.width, thumbnail.height, &thumbnail.pixels)?;
+                    write!(
+                        images,
+                        "<td><img src=\"data:image/png;base64,{}\" width=\"{}\" height=\"{}\"></td>",
+                        base64(&png),
+                        thumbnail.width,
+                        thumbnail.height
+                    )?;
+                }
+
+                let psnr = metrics.psnr.overall;
+                writeln!(
+                    rows,
+                    concat!(
+                        "<tr><td>{}</td>{}",
+                        "<td data-value=\"{}\">{}</td><td data-value=\"{}\">{:.4}</td>",
+                        "<td data-value=\"{}\">{:.4}</td><td data-value=\"{}\">{}</td></tr>",
+                    ),
+                    escape(name),
+                    images,
+                    // Identical images have an infinite PSNR, which JavaScript spells out.
+                    if psnr.is_finite() {
+                        psnr.to_string()
+                    } else {
+                        "Infinity".to_string()
+                    },
+                    if psnr.is_finite() {
+                        format!("{psnr:.2}")
+                    } else {
+                        "&infin;".to_string()
+                    },
+                    metrics.ssim,
+                    metrics.ssim,
+                    metrics.max_error,
+                    metrics.max_error,
+                    metrics.changed_pixels,
+                    metrics.changed_pixels,
+                )?;
+            }
+            ReportEntry::Failed { name, reason } => {
+                writeln!(
+                    rows,
+                    "<tr class=\"failed\"><td>{}</td><td colspan=\"7\">{}</td></tr>",
+                    escape(name),
+                    escape(reason)
+                )?;
+            }
+        }
+    }
+
+    Ok(format!(
+        concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n",
+            "<title>Frame comparison report</title>\n<style>{style}</style>\n</head>\n<body>\n",
+            "<h1>Frame comparison report</h1>\n",
+            "<p>A: {a}<br>B: {b}</p>\n",
+            "<p>{count} images, {changed} changed, {failed} failed.</p>\n",
+            "<table>\n<thead><tr><th>Name</th><th>A</th><th>B</th><th>Difference</th>",
+            "<th class=\"sortable\" data-order=\"1\">PSNR (dB)</th>",
+            "<th class=\"sortable\" data-order=\"1\">SSIM</th>",
+            "<th class=\"sortable\" data-order=\"-1\">Max error</th>",
+            "<th class=\"sortable\" data-order=\"-1\">Changed pixels</th></tr></thead>\n",
+            "<tbody>\n{rows}</tbody>\n</table>\n<script>{script}</script>\n</body>\n</html>\n",
+        ),
+        style = STYLE,
+        script = SCRIPT,
+        a = escape(&a.display().to_string()),
+        b = escape(&b.display().to_string()),
+        count = entries.len(),
+        changed = changed,
+        failed = failed,
+        rows = rows,
+    ))
+}
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: right; }
+td:first-child { text-align: left; }
+th.sortable { cursor: pointer; text-decoration: underline dotted; }
+tr.failed td { background: #fdd; text-align: left; }
+img { display: block; }
+";
+
+// Sorts the rows by the clicked column, worst first: ascending for PSNR and SSIM,
+// descending for the errors. Failed rows have no values and always stay on top.
+const SCRIPT: &str = "
+document.querySelectorAll('th.sortable').forEach(th => {
+  th.addEventListener('click', () => {
+    const column = th.cellIndex;
+    const order = Number(th.dataset.order);
+    const tbody = document.querySelector('tbody');
+    const value = row => {
+      const cell = row.cells[column];
+      return cell && cell.dataset.value !== undefined ? Number(cell.dataset.value) : null;
+    };
+    const rows = Array.from(tbody.rows).sort((a, b) => {
+      const [va, vb] = [value(a), value(b)];
+      if (va === null || vb === null) return (va === null ? 0 : 1) - (vb === null ? 0 : 1);
+      return order * (va - vb);
+    });
+    rows.forEach(row => tbody.appendChild(row));
+  });
+});
+";
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Standard base64 with padding, for the data URIs of the thumbnails.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uniform(width: u32, height: u32, color: [u8; 4]) -> Image {
+        Image {
+            width,
+            height,
+            pixels: color.repeat((width * height) as usize),
+        }
+    }
+
+    #[test]
+    fn base64_pads_to_whole_quads() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xfb, 0xff, 0xfe]), "+//+");
+    }
+
+    #[test]
+    fn thumbnail_reduces_by_an_integer_factor() {
+        let image = uniform(512, 300, [0, 0, 0, 255]);
+        let small = thumbnail(&image, 256);
+        assert_eq!((small.width, small.height), (256, 150));
+        assert_eq!(small.pixels.len(), 256 * 150 * 4);
+
+        // The factor is rounded up, so the thumbnail never exceeds the maximum width.
+        let image = uniform(513, 3, [0, 0, 0, 255]);
+        let small = thumbnail(&image, 256);
+        assert_eq!((small.width, small.height), (171, 1));
+    }
+
+    #[test]
+    fn thumbnail_keeps_small_images() {
+        let image = uniform(100, 50, [1, 2, 3, 4]);
+        let small = thumbnail(&image, 256);
+        assert_eq!((small.width, small.height), (100, 50));
+        assert_eq!(small.pixels, image.pixels);
+    }
+
+    #[test]
+    fn thumbnail_keeps_uniform_colors() {
+        let image = uniform(64, 64, [200, 100, 50, 128]);
+        let small = thumbnail(&image, 16);
+        assert_eq!((small.width, small.height), (16, 16));
+        assert!(
+            small
+                .pixels
+                .chunks_exact(4)
+                .all(|p| p == [200, 100, 50, 128])
+        );
+    }
+
+    #[test]
+    fn thumbnail_averages_in_linear_space() {
+        let image = Image {
+            width: 2,
+            height: 1,
+            pixels: vec![0, 0, 0, 0, 255, 255, 255, 255],
+        };
+        let small = thumbnail(&image, 1);
+        // Half of linear white is brighter than 128 in sRGB, alpha is averaged as is.
+        assert_eq!(small.pixels, [188, 188, 188, 128]);
+    }
+
+    #[test]
+    fn escape_html() {
+        assert_eq!(escape("a<b>&\"c\""), "a&lt;b&gt;&amp;&quot;c&quot;");
+    }
+}
@@ -45,9 +45,11 @@ pub fn create_depth_objects(
         // same depth format
         format,
         vk::ImageTiling::OPTIMAL,
+        // Transfer source, so the raw depth values can be captured.
         vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
             | vk::ImageUsageFlags::SAMPLED
-            | vk::ImageUsageFlags::INPUT_ATTACHMENT,
+            | vk::ImageUsageFlags::INPUT_ATTACHMENT
+            | vk::ImageUsageFlags::TRANSFER_SRC,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
     )?;
 
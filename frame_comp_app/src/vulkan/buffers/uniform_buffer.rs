@@ -8,6 +8,15 @@ use super::buffer::create_buffer;
 
 pub type Mat4 = cgmath::Matrix4<f32>;
 
+/// The default position of the camera, which always looks at the origin.
+pub const CAMERA_EYE: [f32; 3] = [0.0, 2.5, 2.5];
+/// The vertical field of view of the camera in degrees.
+pub const FIELD_OF_VIEW: f32 = 45.0;
+/// The distance of the near clipping plane, which maps to depth 0.0.
+pub const NEAR_PLANE: f32 = 2.5;
+/// The distance of the far clipping plane, which maps to depth 1.0.
+pub const FAR_PLANE: f32 = 4.0;
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub struct UniformBufferObject {
@@ -210,3 +219,40 @@ pub fn create_descriptor_sets(device: &Device, data: &mut AppData) -> Result<()>
 
     Ok(())
 }
+
+/// The projection matrix of the camera for the given extent.
+pub fn projection(extent: vk::Extent2D) -> Mat4 {
+    // Mat4::new constructs the matrix in a column-major order, so the matrix look like
+    // [1,  0, 0  , 0  ]
+    // [0, -1, 0  , 0  ]
+    // [0,  0, 0.5, 0.5]
+    // [0,  0, 0  , 1  ]
+    let correction = Mat4::new(
+        1.0,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        -1.0,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        1.0 / 2.0,
+        0.0,
+        0.0,
+        0.0,
+        1.0 / 2.0,
+        1.0,
+    );
+
+    // cgmath was originally designed for OpenGL, where the Y coordinate of the clip coordinates
+    // is inverted. This is the easiest way to compensate it.
+    correction
+        * cgmath::perspective(
+            cgmath::Deg(FIELD_OF_VIEW),
+            extent.width as f32 / extent.height as f32,
+            NEAR_PLANE,
+            FAR_PLANE,
+        )
+}
@@ -5,6 +5,7 @@ use rtcmp::CompareInfo;
 
 use crate::app::AppData;
 
+use super::query::TIMESTAMPS_PER_FRAME;
 use super::queue::QueueFamilyIndices;
 
 /// A command pool is an object used to manage the memory allocation of command buffers.
@@ -110,6 +111,17 @@ pub fn create_command_buffers(device: &Device, data: &mut AppData) -> Result<()>
         unsafe {
             device.begin_command_buffer(*command_buffer, &info)?;
 
+            // Queries must be reset outside of a render pass before they are written again.
+            let first_query = i as u32 * TIMESTAMPS_PER_FRAME;
+            if !data.comparator_query_pool.is_null() {
+                device.cmd_reset_query_pool(
+                    *command_buffer,
+                    data.comparator_query_pool,
+                    first_query,
+                    TIMESTAMPS_PER_FRAME,
+                );
+            }
+
             device.cmd_bind_vertex_buffers(*command_buffer, 0, &[data.vertex_buffer], &[0]);
             device.cmd_bind_index_buffer(
                 *command_buffer,
@@ -175,7 +187,28 @@ pub fn create_command_buffers(device: &Device, data: &mut AppData) -> Result<()>
                     .divider_position(data.vbar_percentage)
                     .build()?;
 
+                // Written once the scene render pass above has completed, so the measured
+                // duration doesn't include the tail of the scene rendering.
+                if !data.comparator_query_pool.is_null() {
+                    device.cmd_write_timestamp(
+                        *command_buffer,
+                        vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                        data.comparator_query_pool,
+                        first_query,
+                    );
+                }
+
                 comparators[i].compare(&compare_info)?;
+
+                // Written once all the comparator commands have completed.
+                if !data.comparator_query_pool.is_null() {
+                    device.cmd_write_timestamp(
+                        *command_buffer,
+                        vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                        data.comparator_query_pool,
+                        first_query + 1,
+                    );
+                }
             }
 
             device.end_command_buffer(*command_buffer)?;
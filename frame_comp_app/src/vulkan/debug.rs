@@ -8,7 +8,12 @@ lazy_static::lazy_static! {
     static ref MESSAGE_COUNT: Mutex<HashMap<String, u32>> = Mutex::new(HashMap::new());
 }
 
-pub extern "system" fn debug_callback(
+/// Logs the messages of the validation layers, each at most 5 times.
+///
+/// # Safety
+///
+/// Only to be called by Vulkan, with `data` pointing to valid callback data.
+pub unsafe extern "system" fn debug_callback(
     severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     type_: vk::DebugUtilsMessageTypeFlagsEXT,
     data: *const vk::DebugUtilsMessengerCallbackDataEXT,
@@ -1,8 +1,8 @@
 use std::collections::HashSet;
 
-use super::physical_device::DEVICE_EXTENSIONS;
+use super::physical_device::required_device_extensions;
 use crate::app::AppData;
-use crate::app::{PORTABILITY_MACOS_VERSION, VALIDATION_ENABLED, VALIDATION_LAYER};
+use crate::app::{PORTABILITY_MACOS_VERSION, VALIDATION_LAYER};
 use crate::vulkan::queue::*;
 use anyhow::Result;
 use vulkanalia::prelude::v1_3::*;
@@ -28,13 +28,14 @@ pub fn create_logical_device(
         })
         .collect::<Vec<_>>();
 
-    let layers = if VALIDATION_ENABLED {
+    let layers = if data.config.validation {
         vec![VALIDATION_LAYER.as_ptr()]
     } else {
         vec![]
     };
 
-    let mut extensions = DEVICE_EXTENSIONS
+    let required_extensions = required_device_extensions(data);
+    let mut extensions = required_extensions
         .iter()
         .map(|e| e.as_ptr())
         .collect::<Vec<_>>();
@@ -61,5 +62,5 @@ pub fn create_logical_device(
         device
     };
 
-    return Ok(device);
+    Ok(device)
 }
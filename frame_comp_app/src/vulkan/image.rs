@@ -15,9 +15,9 @@ pub fn create_texture_image(
     device: &Device,
     data: &mut AppData,
 ) -> Result<()> {
-    let image_path = "/home/daniel/Art/viking_room.png";
+    let image_path = &data.config.texture_path;
     let image = File::open(image_path)
-        .expect(format!("Failed to open the image resource {image_path}").as_str());
+        .map_err(|e| anyhow!("Failed to open the texture {}: {}", image_path.display(), e))?;
 
     let decoder = png::Decoder::new(std::io::BufReader::new(image));
     let mut reader = decoder.read_info()?;
@@ -608,7 +608,8 @@ pub fn create_color_objects(
             data.swapchain_format,
             vk::ImageTiling::OPTIMAL,
             vk::ImageUsageFlags::COLOR_ATTACHMENT // must be usable as a render target
-            | vk::ImageUsageFlags::SAMPLED, // The comparator will sample from it
+            | vk::ImageUsageFlags::SAMPLED // The comparator will sample from it
+            | vk::ImageUsageFlags::TRANSFER_SRC, // and it can be captured
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
         )?;
 
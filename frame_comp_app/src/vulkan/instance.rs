@@ -1,6 +1,6 @@
 use super::debug::debug_callback;
 use crate::app::AppData;
-use crate::app::{PORTABILITY_MACOS_VERSION, VALIDATION_ENABLED, VALIDATION_LAYER};
+use crate::app::{PORTABILITY_MACOS_VERSION, VALIDATION_LAYER};
 use anyhow::Result;
 use log::*;
 use std::collections::HashSet;
@@ -12,7 +12,14 @@ use winit::window::Window;
 pub const INSTANCE_EXTENSIONS: &[vk::ExtensionName] =
     &[vk::KHR_GET_PHYSICAL_DEVICE_PROPERTIES2_EXTENSION.name];
 
-pub fn create_instance(window: &Window, entry: &Entry, data: &mut AppData) -> Result<Instance> {
+/// Creates the instance with the extensions needed to present to the given window.
+/// Without a window (in headless mode) no surface extensions are enabled, so devices
+/// and drivers without presentation support can be used.
+pub fn create_instance(
+    window: Option<&Window>,
+    entry: &Entry,
+    data: &mut AppData,
+) -> Result<Instance> {
     let application_info = vk::ApplicationInfo::builder()
         .application_name(b"Vulkan Tutorial\0")
         .application_version(vk::make_version(1, 0, 0))
@@ -27,7 +34,7 @@ pub fn create_instance(window: &Window, entry: &Entry, data: &mut AppData) -> Re
 
     let validation_layer_available = available_layers.contains(&VALIDATION_LAYER);
 
-    let layers = if VALIDATION_ENABLED {
+    let layers = if data.config.validation {
         if validation_layer_available {
             vec![VALIDATION_LAYER.as_ptr()]
         } else {
@@ -38,12 +45,15 @@ pub fn create_instance(window: &Window, entry: &Entry, data: &mut AppData) -> Re
         vec![]
     };
 
-    let mut extensions = vk_window::get_required_instance_extensions(window)
-        .iter()
-        .map(|e| e.as_ptr())
-        .collect::<Vec<_>>();
+    let mut extensions = match window {
+        Some(window) => vk_window::get_required_instance_extensions(window)
+            .iter()
+            .map(|e| e.as_ptr())
+            .collect::<Vec<_>>(),
+        None => vec![],
+    };
 
-    if VALIDATION_ENABLED {
+    if data.config.validation {
         extensions.push(vk::EXT_DEBUG_UTILS_EXTENSION.name.as_ptr());
     }
 
@@ -74,13 +84,13 @@ pub fn create_instance(window: &Window, entry: &Entry, data: &mut AppData) -> Re
         )
         .user_callback(Some(debug_callback));
 
-    if VALIDATION_ENABLED {
+    if data.config.validation {
         info = info.push_next(&mut debug_info);
     }
 
     let instance = unsafe { entry.create_instance(&info, None) }?;
 
-    if VALIDATION_ENABLED {
+    if data.config.validation {
         let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
             .message_severity(vk::DebugUtilsMessageSeverityFlagsEXT::all())
             .message_type(
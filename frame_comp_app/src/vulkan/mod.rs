@@ -7,9 +7,12 @@ pub mod framebuffer;
 pub mod image;
 pub mod instance;
 pub mod model;
+pub mod offscreen;
 pub mod physical_device;
 pub mod pipeline;
+pub mod query;
 pub mod queue;
+pub mod readback;
 pub mod render_pass;
 pub mod swapchain;
 pub mod synchronization;
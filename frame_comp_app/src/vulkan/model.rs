@@ -1,13 +1,16 @@
 use std::{collections::HashMap, fs::File, io::BufReader};
 
 use crate::app::AppData;
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use cgmath::{vec2, vec3};
 
 use super::vertex::Vertex;
 
 pub fn load_model(data: &mut AppData) -> Result<()> {
-    let mut reader = BufReader::new(File::open("/home/daniel/Art/viking_room.obj")?);
+    let path = &data.config.model_path;
+    let file = File::open(path)
+        .map_err(|e| anyhow!("Failed to open the model {}: {}", path.display(), e))?;
+    let mut reader = BufReader::new(file);
 
     // We are interested only in the Vec<Model>, not in the Vec<Material>
     let (models, _) = tobj::load_obj_buf(
@@ -0,0 +1,53 @@
+use anyhow::Result;
+use vulkanalia::prelude::v1_3::*;
+
+use crate::app::AppData;
+
+use super::image::create_image;
+
+/// The format of the offscreen images, the same one preferred for the swapchain.
+pub const OFFSCREEN_FORMAT: vk::Format = vk::Format::B8G8R8A8_SRGB;
+
+/// Creates the images the app renders into in headless mode, in place of the swapchain
+/// images. Without a window there is no surface to create a swapchain for, so these are
+/// regular images that are never presented, only read back.
+///
+/// The rest of the app treats them like swapchain images: they are stored in
+/// `data.swapchain_images` together with the swapchain format, extent and usage.
+/// There is one image per frame in flight, as nothing has to be acquired from them.
+pub fn create_offscreen_images(
+    instance: &Instance,
+    device: &Device,
+    data: &mut AppData,
+    extent: vk::Extent2D,
+) -> Result<()> {
+    let usage = vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC;
+
+    data.swapchain_images.clear();
+    data.offscreen_images_memory.clear();
+
+    for _ in 0..data.config.frames_in_flight {
+        let (image, memory) = create_image(
+            instance,
+            device,
+            data,
+            extent.width,
+            extent.height,
+            1, // mip levels
+            vk::SampleCountFlags::_1,
+            OFFSCREEN_FORMAT,
+            vk::ImageTiling::OPTIMAL,
+            usage,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        data.swapchain_images.push(image);
+        data.offscreen_images_memory.push(memory);
+    }
+
+    data.swapchain_format = OFFSCREEN_FORMAT;
+    data.swapchain_extent = extent;
+    data.swapchain_usage = usage;
+
+    Ok(())
+}
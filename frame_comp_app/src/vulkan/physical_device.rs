@@ -4,43 +4,100 @@ use super::errors::SuitabilityError;
 use super::queue::QueueFamilyIndices;
 use super::swapchain::SwapchainSupport;
 use crate::app::AppData;
+use crate::config::DeviceSelector;
 use anyhow::{Result, anyhow};
 use log::*;
 use vulkanalia::prelude::v1_3::*;
 
 pub const DEVICE_EXTENSIONS: &[vk::ExtensionName] = &[
-    vk::KHR_SWAPCHAIN_EXTENSION.name,
     vk::KHR_DEPTH_STENCIL_RESOLVE_EXTENSION.name,
     vk::KHR_CREATE_RENDERPASS2_EXTENSION.name,
     vk::KHR_MULTIVIEW_EXTENSION.name,
     vk::KHR_MAINTENANCE2_EXTENSION.name,
 ];
 
+/// The device extensions to enable: `DEVICE_EXTENSIONS`, plus the swapchain extension
+/// if there is a surface to present to.
+pub fn required_device_extensions(data: &AppData) -> Vec<vk::ExtensionName> {
+    let mut extensions = DEVICE_EXTENSIONS.to_vec();
+    if !data.surface.is_null() {
+        extensions.push(vk::KHR_SWAPCHAIN_EXTENSION.name);
+    }
+    extensions
+}
+
 pub fn pick_physical_device(instance: &Instance, data: &mut AppData) -> Result<()> {
-    unsafe {
-        for physical_device in instance.enumerate_physical_devices()? {
-            let mut properties = vk::PhysicalDeviceProperties2::default();
-            instance.get_physical_device_properties2(physical_device, &mut properties);
-
-            let properties = properties.properties;
-
-            if let Err(error) = check_physical_device(instance, data, physical_device) {
-                warn!(
-                    "Skipping physical device ('{}'): {}",
-                    properties.device_name, error
-                );
-            } else {
-                info!("Selected physical device  ('{}').", properties.device_name);
-                data.physical_device = physical_device;
-                data.msaa_samples = get_max_msaa_samples(instance, data);
-                return Ok(());
-            }
+    let physical_devices = unsafe { instance.enumerate_physical_devices() }?;
+    let names = physical_devices
+        .iter()
+        .map(|d| unsafe { instance.get_physical_device_properties(*d) }.device_name)
+        .collect::<Vec<_>>();
+
+    // A device selected in the config is used even if it is not the first suitable one,
+    // and an error explains why it can't be used.
+    if let Some(selector) = &data.config.device {
+        let index = match selector {
+            DeviceSelector::Index(index) => (*index < names.len()).then_some(*index),
+            DeviceSelector::Name(name) => names
+                .iter()
+                .position(|n| n.to_string().to_lowercase().contains(&name.to_lowercase())),
+        }
+        .ok_or_else(|| {
+            let available = names
+                .iter()
+                .enumerate()
+                .map(|(i, n)| format!("{i}: {n}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            anyhow!("No physical device matches {selector:?}, the devices are {available}.")
+        })?;
+
+        check_physical_device(instance, data, physical_devices[index]).map_err(|error| {
+            anyhow!(
+                "The physical device '{}' can't be used: {}",
+                names[index],
+                error
+            )
+        })?;
+        return select_physical_device(instance, data, physical_devices[index]);
+    }
+
+    for (physical_device, name) in physical_devices.into_iter().zip(names) {
+        if let Err(error) = check_physical_device(instance, data, physical_device) {
+            warn!("Skipping physical device ('{}'): {}", name, error);
+        } else {
+            return select_physical_device(instance, data, physical_device);
         }
     }
 
     Err(anyhow!("Failed to find a suitable physical device."))
 }
 
+fn select_physical_device(
+    instance: &Instance,
+    data: &mut AppData,
+    physical_device: vk::PhysicalDevice,
+) -> Result<()> {
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    info!("Selected physical device  ('{}').", properties.device_name);
+
+    data.physical_device = physical_device;
+    data.physical_device_properties = properties;
+    data.msaa_samples = match data.config.samples {
+        Some(samples) if get_supported_msaa_samples(instance, data).contains(samples) => samples,
+        Some(samples) => {
+            return Err(anyhow!(
+                "The physical device supports at most {} samples, {} were requested.",
+                get_max_msaa_samples(instance, data).bits(),
+                samples.bits()
+            ));
+        }
+        None => get_max_msaa_samples(instance, data),
+    };
+
+    Ok(())
+}
+
 pub fn check_physical_device(
     instance: &Instance,
     data: &AppData,
@@ -49,14 +106,16 @@ pub fn check_physical_device(
     // Check if the physical device contains the needed queue indices (for graphics and presentation queues)
     QueueFamilyIndices::get(instance, data, physical_device)?;
 
-    // Check if the physical device supports swapchains
-    let support = SwapchainSupport::get(instance, data, physical_device)?;
-    if support.formats.is_empty() || support.present_modes.is_empty() {
-        return Err(anyhow!(SuitabilityError("Insufficient swapchain support.")));
+    // Check if the physical device supports swapchains for the surface, if there is one
+    if !data.surface.is_null() {
+        let support = SwapchainSupport::get(instance, data, physical_device)?;
+        if support.formats.is_empty() || support.present_modes.is_empty() {
+            return Err(anyhow!(SuitabilityError("Insufficient swapchain support.")));
+        }
     }
 
     // Check if the physical device has the required extensions
-    check_physical_device_extensions(instance, physical_device)?;
+    check_physical_device_extensions(instance, data, physical_device)?;
 
     let features = unsafe { instance.get_physical_device_features(physical_device) };
     if features.sampler_anisotropy != vk::TRUE {
@@ -72,6 +131,7 @@ pub fn check_physical_device(
 
 pub fn check_physical_device_extensions(
     instance: &Instance,
+    data: &AppData,
     physical_device: vk::PhysicalDevice,
 ) -> Result<()> {
     let extensions =
@@ -80,7 +140,7 @@ pub fn check_physical_device_extensions(
             .map(|e| e.extension_name)
             .collect::<HashSet<_>>();
 
-    if DEVICE_EXTENSIONS.iter().all(|e| {
+    if required_device_extensions(data).iter().all(|e| {
         if extensions.contains(e) {
             true
         } else {
@@ -97,17 +157,7 @@ pub fn check_physical_device_extensions(
 }
 
 pub fn get_max_msaa_samples(instance: &Instance, data: &AppData) -> vk::SampleCountFlags {
-    let mut properties = vk::PhysicalDeviceProperties2::default();
-
-    unsafe {
-        instance.get_physical_device_properties2(data.physical_device, &mut properties);
-    }
-
-    //let properties = unsafe { instance.get_physical_device_properties(data.physical_device) };
-    let properties = properties.properties;
-
-    let counts = properties.limits.framebuffer_color_sample_counts
-        & properties.limits.framebuffer_depth_sample_counts;
+    let counts = get_supported_msaa_samples(instance, data);
 
     [
         vk::SampleCountFlags::_64,
@@ -121,3 +171,18 @@ pub fn get_max_msaa_samples(instance: &Instance, data: &AppData) -> vk::SampleCo
     .find(|c| counts.contains(*c))
     .unwrap_or(vk::SampleCountFlags::_1)
 }
+
+/// The sample counts supported by both the color and the depth attachments.
+pub fn get_supported_msaa_samples(instance: &Instance, data: &AppData) -> vk::SampleCountFlags {
+    let mut properties = vk::PhysicalDeviceProperties2::default();
+
+    unsafe {
+        instance.get_physical_device_properties2(data.physical_device, &mut properties);
+    }
+
+    //let properties = unsafe { instance.get_physical_device_properties(data.physical_device) };
+    let properties = properties.properties;
+
+    properties.limits.framebuffer_color_sample_counts
+        & properties.limits.framebuffer_depth_sample_counts
+}
@@ -0,0 +1,102 @@
+use anyhow::Result;
+use log::warn;
+use vulkanalia::prelude::v1_3::*;
+
+use crate::app::AppData;
+
+use super::commands::{begin_single_time_commands, end_single_time_commands};
+use super::queue::QueueFamilyIndices;
+
+/// Number of timestamps written per command buffer: one before and one after the comparison.
+pub const TIMESTAMPS_PER_FRAME: u32 = 2;
+
+/// A query pool is a collection of queries of a single type that the GPU writes results into.
+/// Timestamp queries record the value of a GPU clock once all previously submitted commands
+/// have reached the given pipeline stage. The difference between two timestamps multiplied
+/// by the device's `timestamp_period` gives the GPU time spent between them in nanoseconds.
+///
+/// We write a pair of timestamps around the commands recorded by the frame comparator,
+/// one pair per command buffer (swapchain image). If the graphics queue doesn't support
+/// timestamps, the pool stays null and the comparator is simply not profiled.
+pub fn create_timestamp_query_pool(
+    instance: &Instance,
+    device: &Device,
+    data: &mut AppData,
+) -> Result<()> {
+    let indices = QueueFamilyIndices::get(instance, data, data.physical_device)?;
+
+    let properties = unsafe { instance.get_physical_device_properties(data.physical_device) };
+    let queue_families =
+        unsafe { instance.get_physical_device_queue_family_properties(data.physical_device) };
+    let valid_bits = queue_families[indices.graphics as usize].timestamp_valid_bits;
+
+    if valid_bits == 0 || properties.limits.timestamp_period == 0.0 {
+        warn!("Timestamp queries are not supported, the comparator won't be profiled.");
+        data.comparator_query_pool = vk::QueryPool::null();
+        return Ok(());
+    }
+
+    data.timestamp_period = properties.limits.timestamp_period;
+    // Only the lower `valid_bits` bits of a timestamp are meaningful.
+    data.timestamp_mask = if valid_bits >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << valid_bits) - 1
+    };
+
+    let query_count = data.swapchain_images.len() as u32 * TIMESTAMPS_PER_FRAME;
+    let info = vk::QueryPoolCreateInfo::builder()
+        .query_type(vk::QueryType::TIMESTAMP)
+        .query_count(query_count);
+
+    data.comparator_query_pool = unsafe { device.create_query_pool(&info, None) }?;
+
+    // Queries have to be reset before their results are read for the first time,
+    // otherwise reading them before the first frame has finished is undefined.
+    let command_buffer = begin_single_time_commands(device, data)?;
+    unsafe {
+        device.cmd_reset_query_pool(command_buffer, data.comparator_query_pool, 0, query_count)
+    };
+    end_single_time_commands(device, data, command_buffer)?;
+
+    Ok(())
+}
+
+/// Reads the timestamps written by the command buffer of the given swapchain image and
+/// returns the GPU time of the comparison in nanoseconds. Returns `None` if the results
+/// are not available yet (the command buffer hasn't finished executing).
+///
+/// Doesn't wait for the results, so it should be called after the fence associated with
+/// the command buffer has been signaled.
+pub fn get_comparator_duration_ns(
+    device: &Device,
+    data: &AppData,
+    image_index: usize,
+) -> Result<Option<f64>> {
+    if data.comparator_query_pool.is_null() {
+        return Ok(None);
+    }
+
+    let mut results = [0u8; 2 * size_of::<u64>()];
+    let result = unsafe {
+        device.get_query_pool_results(
+            data.comparator_query_pool,
+            image_index as u32 * TIMESTAMPS_PER_FRAME,
+            TIMESTAMPS_PER_FRAME,
+            &mut results,
+            size_of::<u64>() as u64,
+            vk::QueryResultFlags::_64,
+        )
+    }?;
+
+    if result != vk::SuccessCode::SUCCESS {
+        return Ok(None);
+    }
+
+    let begin = u64::from_ne_bytes(results[..8].try_into()?) & data.timestamp_mask;
+    let end = u64::from_ne_bytes(results[8..].try_into()?) & data.timestamp_mask;
+
+    let ticks = end.wrapping_sub(begin) & data.timestamp_mask;
+
+    Ok(Some(ticks as f64 * data.timestamp_period as f64))
+}
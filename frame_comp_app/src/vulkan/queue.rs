@@ -7,6 +7,8 @@ use vulkanalia::prelude::v1_3::*;
 #[derive(Copy, Clone, Debug)]
 pub struct QueueFamilyIndices {
     pub graphics: u32,
+    /// In headless mode (without a surface) nothing is presented, so this is the
+    /// graphics queue family.
     pub present: u32,
 }
 
@@ -26,6 +28,17 @@ impl QueueFamilyIndices {
             .position(|p| p.queue_flags.contains(vk::QueueFlags::GRAPHICS))
             .map(|i| i as u32);
 
+        if data.surface.is_null() {
+            return graphics
+                .map(|graphics| Self {
+                    graphics,
+                    present: graphics,
+                })
+                .ok_or_else(|| {
+                    anyhow!(errors::SuitabilityError("Mssing required queue families."))
+                });
+        }
+
         for (index, properties) in properties.iter().enumerate() {
             if (unsafe {
                 instance.get_physical_device_surface_support_khr(
@@ -0,0 +1,207 @@
+use anyhow::{Result, anyhow};
+use vulkanalia::prelude::v1_3::*;
+
+use crate::app::AppData;
+
+use super::buffers::buffer::create_buffer;
+
+/// A host visible buffer that receives the texels of an image copied by the GPU,
+/// so they can be read by the CPU (e.g. for screenshots).
+///
+/// Images with optimal tiling can't be mapped directly as their texels are laid out
+/// in an implementation defined order, so they are copied into a buffer first where
+/// the texels are tightly packed row by row.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReadbackBuffer {
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    pub size: vk::DeviceSize,
+    pub extent: vk::Extent2D,
+    pub format: vk::Format,
+}
+
+impl ReadbackBuffer {
+    pub fn create(
+        instance: &Instance,
+        device: &Device,
+        data: &mut AppData,
+        extent: vk::Extent2D,
+        format: vk::Format,
+    ) -> Result<Self> {
+        let size = extent.width as u64 * extent.height as u64 * bytes_per_texel(format)? as u64;
+
+        let (buffer, memory) = create_buffer(
+            instance,
+            device,
+            data,
+            size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        Ok(Self {
+            buffer,
+            memory,
+            size,
+            extent,
+            format,
+        })
+    }
+
+    /// Records the commands that copy the whole image into this buffer.
+    ///
+    /// The image is transitioned from `layout` to TRANSFER_SRC_OPTIMAL for the copy and back
+    /// again afterwards, so the commands can be inserted between other work on the image.
+    /// `src_stage_mask` and `src_access_mask` describe the last write to the image that the
+    /// copy has to wait for.
+    pub fn record_copy(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        layout: vk::ImageLayout,
+        aspect_mask: vk::ImageAspectFlags,
+        src_stage_mask: vk::PipelineStageFlags,
+        src_access_mask: vk::AccessFlags,
+    ) {
+        // Layout transitions of combined depth/stencil images have to include both aspects,
+        // even though only the depth is copied.
+        let barrier_aspect_mask = match self.format {
+            vk::Format::D32_SFLOAT_S8_UINT | vk::Format::D24_UNORM_S8_UINT => {
+                vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+            }
+            _ => aspect_mask,
+        };
+
+        let subresource = vk::ImageSubresourceRange::builder()
+            .aspect_mask(barrier_aspect_mask)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1);
+
+        let to_transfer = vk::ImageMemoryBarrier::builder()
+            .image(image)
+            .old_layout(layout)
+            .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .subresource_range(subresource)
+            .src_access_mask(src_access_mask)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ);
+
+        let subresource_layers = vk::ImageSubresourceLayers::builder()
+            .aspect_mask(aspect_mask)
+            .mip_level(0)
+            .base_array_layer(0)
+            .layer_count(1);
+
+        let region = vk::BufferImageCopy::builder()
+            .buffer_offset(0)
+            // Tightly packed rows.
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(subresource_layers)
+            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+            .image_extent(vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            });
+
+        // Restore the original layout so the following commands (e.g. presenting)
+        // find the image as they expect it.
+        let to_original = vk::ImageMemoryBarrier::builder()
+            .image(image)
+            .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .new_layout(layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .subresource_range(subresource)
+            .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .dst_access_mask(vk::AccessFlags::empty());
+
+        // Makes the copied texels visible to the host once the fence is signaled.
+        let to_host = vk::BufferMemoryBarrier::builder()
+            .buffer(self.buffer)
+            .offset(0)
+            .size(self.size)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ);
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage_mask,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[] as &[vk::MemoryBarrier],
+                &[] as &[vk::BufferMemoryBarrier],
+                &[to_transfer],
+            );
+
+            device.cmd_copy_image_to_buffer(
+                command_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.buffer,
+                &[region],
+            );
+
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE | vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[] as &[vk::MemoryBarrier],
+                &[to_host],
+                &[to_original],
+            );
+        }
+    }
+
+    /// Copies the texels to host memory. The commands recorded by `record_copy` must have
+    /// finished executing.
+    pub fn read(&self, device: &Device) -> Result<Vec<u8>> {
+        let mut texels = vec![0u8; self.size as usize];
+
+        unsafe {
+            let memory =
+                device.map_memory(self.memory, 0, self.size, vk::MemoryMapFlags::empty())?;
+            std::ptr::copy_nonoverlapping(memory.cast(), texels.as_mut_ptr(), texels.len());
+            device.unmap_memory(self.memory);
+        }
+
+        Ok(texels)
+    }
+
+    pub fn destroy(&self, device: &Device) {
+        unsafe {
+            device.destroy_buffer(self.buffer, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
+/// The size of a single texel of the formats that can be read back.
+pub fn bytes_per_texel(format: vk::Format) -> Result<u32> {
+    match format {
+        vk::Format::B8G8R8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::R8G8B8A8_UNORM => Ok(4),
+        vk::Format::R16G16B16A16_UNORM | vk::Format::R16G16B16A16_SFLOAT => Ok(8),
+        vk::Format::R32G32B32A32_SFLOAT => Ok(16),
+        // Copies of the depth aspect are 4 bytes per texel for all of these, the stencil
+        // values are not copied and the upper 8 bits of D24 are undefined.
+        vk::Format::D32_SFLOAT | vk::Format::D32_SFLOAT_S8_UINT | vk::Format::D24_UNORM_S8_UINT => {
+            Ok(4)
+        }
+        _ => Err(anyhow!(
+            "Reading back {:?} images is not supported.",
+            format
+        )),
+    }
+}
@@ -1,5 +1,5 @@
 use crate::app::AppData;
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use vulkanalia::prelude::v1_3::*;
 use vulkanalia::vk::KhrSwapchainExtensionDeviceCommands;
 use vulkanalia::{vk, vk::KhrSurfaceExtensionInstanceCommands};
@@ -66,7 +66,8 @@ pub fn create_swapchain(
     let support = SwapchainSupport::get(instance, data, data.physical_device)?;
 
     let surface_format = get_swapchain_surface_format(&support.formats);
-    let present_mode = get_swapchain_present_mode(&support.present_modes);
+    let present_mode =
+        get_swapchain_present_mode(&support.present_modes, data.config.present_mode)?;
     let extent = get_swapchain_extent(window, support.capabilities);
 
     let mut image_count = support.capabilities.min_image_count + 1;
@@ -85,6 +86,16 @@ pub fn create_swapchain(
         vk::SharingMode::EXCLUSIVE
     };
 
+    // The swapchain images are copied to host memory for screenshots, if supported.
+    let mut image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT;
+    if support
+        .capabilities
+        .supported_usage_flags
+        .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+    {
+        image_usage |= vk::ImageUsageFlags::TRANSFER_SRC;
+    }
+
     let info = vk::SwapchainCreateInfoKHR::builder()
         .surface(data.surface)
         .min_image_count(image_count)
@@ -92,7 +103,7 @@ pub fn create_swapchain(
         .image_color_space(surface_format.color_space)
         .image_extent(extent)
         .image_array_layers(1)
-        .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+        .image_usage(image_usage)
         .image_sharing_mode(image_sharing_mode)
         .queue_family_indices(&queue_family_indices)
         .pre_transform(support.capabilities.current_transform)
@@ -107,6 +118,7 @@ pub fn create_swapchain(
     }
     data.swapchain_format = surface_format.format;
     data.swapchain_extent = extent;
+    data.swapchain_usage = image_usage;
 
     Ok(())
 }
@@ -122,12 +134,25 @@ pub fn get_swapchain_surface_format(formats: &[vk::SurfaceFormatKHR]) -> vk::Sur
         .unwrap_or_else(|| formats[0])
 }
 
-pub fn get_swapchain_present_mode(present_modes: &[vk::PresentModeKHR]) -> vk::PresentModeKHR {
-    present_modes
-        .iter()
-        .cloned()
-        .find(|m| *m == vk::PresentModeKHR::MAILBOX)
-        .unwrap_or(vk::PresentModeKHR::FIFO)
+/// Returns the requested present mode, which the surface must support, or mailbox if
+/// nothing was requested. FIFO is the fallback as it is the only mode that is always supported.
+pub fn get_swapchain_present_mode(
+    present_modes: &[vk::PresentModeKHR],
+    requested: Option<vk::PresentModeKHR>,
+) -> Result<vk::PresentModeKHR> {
+    match requested {
+        Some(mode) if present_modes.contains(&mode) => Ok(mode),
+        Some(mode) => Err(anyhow!(
+            "The present mode {:?} isn't supported by the surface, only {:?}.",
+            mode,
+            present_modes
+        )),
+        None => Ok(present_modes
+            .iter()
+            .cloned()
+            .find(|m| *m == vk::PresentModeKHR::MAILBOX)
+            .unwrap_or(vk::PresentModeKHR::FIFO)),
+    }
 }
 
 pub fn get_swapchain_extent(
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use vulkanalia::prelude::v1_3::*;
 
-use crate::app::{AppData, MAX_FRAMES_IN_FLIGHT};
+use crate::app::AppData;
 
 /// Semaphores
 ///
@@ -65,7 +65,7 @@ pub fn create_sync_objects(device: &Device, data: &mut AppData) -> Result<()> {
     let fence_info = vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
 
     unsafe {
-        for _ in 0..MAX_FRAMES_IN_FLIGHT {
+        for _ in 0..data.config.frames_in_flight {
             data.image_available_semaphores
                 .push(device.create_semaphore(&semaphore_info, None)?);
             data.render_finished_semaphores